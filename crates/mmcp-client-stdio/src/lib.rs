@@ -1 +1,112 @@
-// todo
+use std::{pin::Pin, process::Stdio};
+
+use futures::{Stream, StreamExt as _, TryStreamExt as _, channel::mpsc};
+use mmcp_protocol::{
+    mcp::JSONRPCMessage,
+    port::{RPCPort, RPCSink},
+};
+use mmcp_rpc::RPCRuntime;
+use tokio::{
+    io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader},
+    process::{Child, ChildStderr, Command},
+};
+use tokio_stream::wrappers::LinesStream;
+
+type MessageStream = Pin<Box<dyn Stream<Item = anyhow::Result<JSONRPCMessage>> + Send + Sync>>;
+
+/// An [`RPCPort`] to a server running as a child process, talking newline-delimited JSON over its
+/// stdin and stdout.
+///
+/// The child is killed when the port is dropped, and reaped by the tokio runtime.
+pub struct StdioClientRpc {
+    rpc: RPCRuntime<mpsc::Sender<JSONRPCMessage>, MessageStream>,
+    child: Child,
+}
+
+/// Spawn the server and connect to its stdin and stdout.
+///
+/// The stderr of the server is inherited unless configured on the command, so it is forwarded to
+/// the stderr of this process by default. Set `Stdio::piped()` and take it with
+/// [`StdioClientRpc::take_stderr`] to capture it.
+pub fn stdio_client_rpc(command: impl Into<Command>) -> anyhow::Result<StdioClientRpc> {
+    let mut child = command
+        .into()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let stdout = child.stdout.take().expect("stdout is piped");
+
+    let (tx, mut rx) = mpsc::channel::<JSONRPCMessage>(100);
+    let stream = LinesStream::new(BufReader::new(stdout).lines())
+        .map_err(anyhow::Error::from)
+        .try_filter_map(|line| async move {
+            match serde_json::from_str::<JSONRPCMessage>(&line) {
+                Ok(message) => Ok(Some(message)),
+                Err(e) => {
+                    tracing::warn!("Error parsing JSON: {}: {}", e, line);
+                    Ok(None)
+                }
+            }
+        });
+
+    // forward the channel to the stdin, which closes it once all the senders are dropped.
+    tokio::spawn(async move {
+        while let Some(message) = rx.next().await {
+            let mut json = serde_json::to_string(&message).unwrap();
+            json.push('\n');
+            if stdin.write_all(json.as_bytes()).await.is_err() || stdin.flush().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(StdioClientRpc {
+        rpc: RPCRuntime::new(tx, Box::pin(stream)),
+        child,
+    })
+}
+
+impl StdioClientRpc {
+    /// Take the stderr of the server, if it was set to `Stdio::piped()` on the command.
+    pub fn take_stderr(&mut self) -> Option<ChildStderr> {
+        self.child.stderr.take()
+    }
+
+    /// The OS-assigned process identifier of the server, or `None` once it has exited.
+    pub fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+}
+
+impl RPCPort for StdioClientRpc {
+    fn sink(&self) -> impl RPCSink + Clone + Send + 'static {
+        self.rpc.sink()
+    }
+
+    fn progress(&mut self) -> impl Future<Output = anyhow::Result<Option<JSONRPCMessage>>> + Send {
+        self.rpc.progress()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stdio_client_rpc() {
+        // `cat` sends back whatever it receives
+        let mut port = stdio_client_rpc(Command::new("cat")).unwrap();
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized",
+        });
+        port.sink()
+            .send_message(serde_json::from_value(notification.clone()).unwrap())
+            .await
+            .unwrap();
+        let message = port.progress().await.unwrap().unwrap();
+        assert_eq!(serde_json::to_value(message).unwrap(), notification);
+    }
+}
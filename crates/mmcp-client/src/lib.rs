@@ -1 +1,728 @@
-// todo
+use std::{pin::Pin, sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+use mmcp_protocol::{
+    ProtocolVersion,
+    consts::error_codes,
+    mcp::{
+        CallToolRequestParams, CallToolResult, ClientCapabilities, CompleteRequestParams,
+        CompleteRequestParamsArgument, CompleteRequestParamsRef, CompleteResult,
+        CreateMessageRequestParams, CreateMessageResult, GetPromptRequestParams, GetPromptResult,
+        Implementation, InitializeRequestParams, InitializeResult, JSONRPCError, JSONRPCMessage,
+        JSONRPCNotification, JSONRPCRequest, JSONRPCResponse, JsonrpcErrorError,
+        ListPromptsRequestParams, ListPromptsResult, ListResourceTemplatesRequestParams,
+        ListResourceTemplatesResult, ListResourcesRequestParams, ListResourcesResult,
+        ListRootsResult, ListToolsRequestParams, ListToolsResult, LoggingLevel,
+        ReadResourceRequestParams, ReadResourceResult, RequestId, Root, ServerCapabilities,
+        SetLevelRequestParams,
+    },
+    port::{RPCPort, RPCSink, RequestOptions},
+};
+use mmcp_rpc::Peer;
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::task::JoinHandle;
+
+type NotificationHandler = Arc<dyn Fn(JSONRPCNotification) + Send + Sync>;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+type SamplingHandler = Arc<
+    dyn Fn(CreateMessageRequestParams) -> BoxFuture<Result<CreateMessageResult, JsonrpcErrorError>>
+        + Send
+        + Sync,
+>;
+
+type RootsHandler = Arc<dyn Fn() -> BoxFuture<Vec<Root>> + Send + Sync>;
+
+/// The handlers of the requests the server may send, besides `ping`.
+#[derive(Clone, Default)]
+struct RequestHandlers {
+    sampling: Option<SamplingHandler>,
+    roots: Option<RootsHandler>,
+}
+
+/// An error responded by the server to a request.
+#[derive(thiserror::Error, Debug, Clone)]
+#[error("server responded with error {code}: {message}")]
+pub struct ServerError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<serde_json::Value>,
+}
+
+impl From<JSONRPCError> for ServerError {
+    fn from(error: JSONRPCError) -> Self {
+        Self {
+            code: error.error.code,
+            message: error.error.message,
+            data: error.error.data,
+        }
+    }
+}
+
+/// Builder for [`MCPClient`], configuring what is sent to the server on initialize
+pub struct MCPClientBuilder {
+    client_info: Implementation,
+    capabilities: ClientCapabilities,
+    on_notification: Option<NotificationHandler>,
+    request_handlers: RequestHandlers,
+    request_options: RequestOptions,
+}
+
+impl MCPClientBuilder {
+    /// Set the capabilities advertised to the server. `sampling` and `roots` are advertised by
+    /// setting their handlers with [`on_sampling`](Self::on_sampling) and
+    /// [`on_list_roots`](Self::on_list_roots), and connecting fails if they are set here without
+    /// one.
+    pub fn with_capabilities(mut self, capabilities: ClientCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Set how long to wait for the response of each request, which is the default of the port
+    /// otherwise. On timeout, the request is cancelled with `notifications/cancelled`. `None`
+    /// waits for the responses without any timeout, e.g. for slow tools.
+    pub fn with_request_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.request_options = match timeout {
+            Some(timeout) => self.request_options.timeout(timeout),
+            None => self.request_options.no_timeout(),
+        };
+        self
+    }
+
+    /// Set a handler called with each notification from the server
+    pub fn on_notification(
+        mut self,
+        handler: impl Fn(JSONRPCNotification) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_notification = Some(Arc::new(handler));
+        self
+    }
+
+    /// Answer `sampling/createMessage` from the server with the handler, and advertise the
+    /// `sampling` capability. An error, e.g. when the user rejects the request, is responded as is.
+    pub fn on_sampling<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(CreateMessageRequestParams) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<CreateMessageResult, JsonrpcErrorError>> + Send + 'static,
+    {
+        self.request_handlers.sampling = Some(Arc::new(move |params| Box::pin(handler(params))));
+        self
+    }
+
+    /// Answer `roots/list` from the server with the roots returned by the handler, and advertise
+    /// the `roots` capability.
+    pub fn on_list_roots<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Vec<Root>> + Send + 'static,
+    {
+        self.request_handlers.roots = Some(Arc::new(move || Box::pin(handler())));
+        self
+    }
+
+    /// The capabilities to advertise, failing if one is set without the handler it needs.
+    fn capabilities(&self) -> anyhow::Result<ClientCapabilities> {
+        let mut capabilities = self.capabilities.clone();
+        match (&capabilities.sampling, &self.request_handlers.sampling) {
+            (Some(_), None) => {
+                return Err(anyhow!(
+                    "the sampling capability requires a handler set with on_sampling"
+                ));
+            }
+            (None, Some(_)) => capabilities.sampling = Some(Default::default()),
+            _ => {}
+        }
+        match (&capabilities.roots, &self.request_handlers.roots) {
+            (Some(_), None) => {
+                return Err(anyhow!(
+                    "the roots capability requires a handler set with on_list_roots"
+                ));
+            }
+            (None, Some(_)) => capabilities.roots = Some(Default::default()),
+            _ => {}
+        }
+        Ok(capabilities)
+    }
+
+    /// Connect to the server over the port, running `initialize` and
+    /// `notifications/initialized`.
+    ///
+    /// The port is driven by a task spawned on the current tokio runtime until the connection is
+    /// closed or the client is dropped.
+    pub async fn connect<P: RPCPort + Send + 'static>(self, port: P) -> anyhow::Result<MCPClient> {
+        let capabilities = self.capabilities()?;
+        let peer = Peer::new(port.sink());
+        let task = tokio::spawn(run(
+            port,
+            peer.clone(),
+            self.on_notification,
+            self.request_handlers,
+        ));
+        let mut client = MCPClient {
+            peer,
+            request_options: self.request_options,
+            initialize_result: None,
+            task,
+        };
+
+        let result: InitializeResult = client
+            .request(
+                "initialize",
+                InitializeRequestParams {
+                    capabilities,
+                    client_info: self.client_info,
+                    protocol_version: ProtocolVersion::LATEST.to_string(),
+                    extra: Default::default(),
+                },
+            )
+            .await?;
+        // Fail early if the server chose a version we cannot speak
+        result.protocol_version.parse::<ProtocolVersion>()?;
+        client.initialize_result = Some(result);
+
+        client
+            .peer
+            .clone()
+            .send_notification("notifications/initialized", serde_json::Value::Null)
+            .await?;
+        Ok(client)
+    }
+}
+
+/// MCP client connected to a server
+pub struct MCPClient {
+    peer: Peer,
+    request_options: RequestOptions,
+    // Only `None` while connecting
+    initialize_result: Option<InitializeResult>,
+    task: JoinHandle<()>,
+}
+
+impl MCPClient {
+    pub fn builder(name: impl Into<String>, version: impl Into<String>) -> MCPClientBuilder {
+        MCPClientBuilder {
+            client_info: Implementation {
+                name: name.into(),
+                version: version.into(),
+                extra: Default::default(),
+            },
+            capabilities: Default::default(),
+            on_notification: None,
+            request_handlers: Default::default(),
+            request_options: Default::default(),
+        }
+    }
+
+    fn initialize_result(&self) -> &InitializeResult {
+        self.initialize_result
+            .as_ref()
+            .expect("client is initialized")
+    }
+
+    /// The capabilities of the server, as responded to `initialize`
+    pub fn server_capabilities(&self) -> &ServerCapabilities {
+        &self.initialize_result().capabilities
+    }
+
+    /// The name and version of the server
+    pub fn server_info(&self) -> &Implementation {
+        &self.initialize_result().server_info
+    }
+
+    /// The protocol version negotiated with the server
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.initialize_result()
+            .protocol_version
+            .parse()
+            .expect("protocol version is validated on initialize")
+    }
+
+    /// The instructions for using the server, if it gave any
+    pub fn instructions(&self) -> Option<&str> {
+        self.initialize_result().instructions.as_deref()
+    }
+
+    /// The sink for sending messages to the server
+    pub fn peer(&self) -> &Peer {
+        &self.peer
+    }
+
+    /// Send a request to the server and wait for the result. JSON-RPC errors are returned as
+    /// [`ServerError`].
+    pub async fn request<T: Serialize + Send, R: DeserializeOwned + Send>(
+        &self,
+        method: &str,
+        params: T,
+    ) -> anyhow::Result<R> {
+        match self
+            .peer
+            .clone()
+            .request_with_options(method, params, self.request_options.clone())
+            .await?
+        {
+            Ok(result) => Ok(result),
+            Err(error) => Err(ServerError::from(error).into()),
+        }
+    }
+
+    pub async fn ping(&self) -> anyhow::Result<()> {
+        self.request::<_, serde_json::Value>("ping", serde_json::Value::Null)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn list_tools(&self, cursor: Option<String>) -> anyhow::Result<ListToolsResult> {
+        self.request(
+            "tools/list",
+            ListToolsRequestParams {
+                cursor,
+                extra: Default::default(),
+            },
+        )
+        .await
+    }
+
+    pub async fn call_tool(
+        &self,
+        name: impl Into<String>,
+        arguments: serde_json::Value,
+    ) -> anyhow::Result<CallToolResult> {
+        let arguments = match arguments {
+            serde_json::Value::Object(arguments) => Some(arguments),
+            serde_json::Value::Null => None,
+            other => return Err(anyhow!("tool arguments must be an object, got {other}")),
+        };
+        self.request(
+            "tools/call",
+            CallToolRequestParams {
+                arguments,
+                name: name.into(),
+                extra: Default::default(),
+            },
+        )
+        .await
+    }
+
+    pub async fn list_resources(
+        &self,
+        cursor: Option<String>,
+    ) -> anyhow::Result<ListResourcesResult> {
+        self.request(
+            "resources/list",
+            ListResourcesRequestParams {
+                cursor,
+                extra: Default::default(),
+            },
+        )
+        .await
+    }
+
+    pub async fn list_resource_templates(
+        &self,
+        cursor: Option<String>,
+    ) -> anyhow::Result<ListResourceTemplatesResult> {
+        self.request(
+            "resources/templates/list",
+            ListResourceTemplatesRequestParams {
+                cursor,
+                extra: Default::default(),
+            },
+        )
+        .await
+    }
+
+    pub async fn read_resource(
+        &self,
+        uri: impl Into<String>,
+    ) -> anyhow::Result<ReadResourceResult> {
+        self.request(
+            "resources/read",
+            ReadResourceRequestParams {
+                uri: uri.into(),
+                extra: Default::default(),
+            },
+        )
+        .await
+    }
+
+    pub async fn list_prompts(&self, cursor: Option<String>) -> anyhow::Result<ListPromptsResult> {
+        self.request(
+            "prompts/list",
+            ListPromptsRequestParams {
+                cursor,
+                extra: Default::default(),
+            },
+        )
+        .await
+    }
+
+    pub async fn get_prompt(
+        &self,
+        name: impl Into<String>,
+        arguments: impl IntoIterator<Item = (String, String)>,
+    ) -> anyhow::Result<GetPromptResult> {
+        let arguments = arguments.into_iter().collect::<indexmap::IndexMap<_, _>>();
+        self.request(
+            "prompts/get",
+            GetPromptRequestParams {
+                arguments: (!arguments.is_empty()).then_some(arguments),
+                name: name.into(),
+                extra: Default::default(),
+            },
+        )
+        .await
+    }
+
+    /// Ask for completion options of an argument of a prompt or resource template.
+    pub async fn complete(
+        &self,
+        reference: CompleteRequestParamsRef,
+        argument: impl Into<String>,
+        value: impl Into<String>,
+    ) -> anyhow::Result<CompleteResult> {
+        self.request(
+            "completion/complete",
+            CompleteRequestParams {
+                argument: CompleteRequestParamsArgument {
+                    name: argument.into(),
+                    value: value.into(),
+                    extra: Default::default(),
+                },
+                r#ref: reference,
+                extra: Default::default(),
+            },
+        )
+        .await
+    }
+
+    pub async fn set_level(&self, level: LoggingLevel) -> anyhow::Result<()> {
+        self.request::<_, serde_json::Value>(
+            "logging/setLevel",
+            SetLevelRequestParams {
+                level,
+                extra: Default::default(),
+            },
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl Drop for MCPClient {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Drive the port, answering requests and passing notifications from the server.
+async fn run<P: RPCPort>(
+    mut port: P,
+    peer: Peer,
+    on_notification: Option<NotificationHandler>,
+    request_handlers: RequestHandlers,
+) {
+    loop {
+        let message = match port.progress().await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::warn!("failed to receive a message from the server: {e}");
+                break;
+            }
+        };
+        match message {
+            JSONRPCMessage::JSONRPCRequest(request) => {
+                let mut peer = peer.clone();
+                let request_handlers = request_handlers.clone();
+                tokio::spawn(async move {
+                    let response = handle_request(&request_handlers, request).await;
+                    let _ = peer.send_message(response).await;
+                });
+            }
+            JSONRPCMessage::JSONRPCNotification(notification) => {
+                if let Some(handler) = &on_notification {
+                    handler(notification);
+                }
+            }
+            // Responses are passed to the waiting requests by the port
+            _ => {}
+        }
+    }
+}
+
+async fn handle_request(handlers: &RequestHandlers, request: JSONRPCRequest) -> JSONRPCMessage {
+    let id = request.id.clone();
+    match (request.method.as_str(), &handlers.sampling, &handlers.roots) {
+        ("ping", _, _) => respond(id, serde_json::json!({})),
+        ("sampling/createMessage", Some(sampling), _) => match params(&request) {
+            Ok(params) => match sampling(params).await {
+                Ok(result) => respond(id, result),
+                Err(error) => error_response(id, error),
+            },
+            Err(e) => error_response(
+                id,
+                error(error_codes::INVALID_PARAMS, format!("invalid params: {e}")),
+            ),
+        },
+        ("roots/list", _, Some(roots)) => respond(
+            id,
+            ListRootsResult {
+                meta: None,
+                roots: roots().await,
+                extra: Default::default(),
+            },
+        ),
+        (method, _, _) => error_response(
+            id,
+            error(
+                error_codes::METHOD_NOT_FOUND,
+                format!("method not found: {method}"),
+            ),
+        ),
+    }
+}
+
+/// The params of the request, deserialized as `T`.
+fn params<T: DeserializeOwned>(request: &JSONRPCRequest) -> serde_json::Result<T> {
+    serde_json::to_value(&request.params).and_then(serde_json::from_value)
+}
+
+fn respond<T: Serialize>(id: RequestId, result: T) -> JSONRPCMessage {
+    match serde_json::to_value(result).and_then(serde_json::from_value) {
+        Ok(result) => JSONRPCMessage::JSONRPCResponse(JSONRPCResponse {
+            id,
+            jsonrpc: Default::default(),
+            result,
+            extra: Default::default(),
+        }),
+        Err(e) => error_response(id, error(error_codes::INTERNAL_ERROR, e.to_string())),
+    }
+}
+
+fn error_response(id: RequestId, error: JsonrpcErrorError) -> JSONRPCMessage {
+    JSONRPCMessage::JSONRPCError(JSONRPCError {
+        error,
+        id,
+        jsonrpc: Default::default(),
+        extra: Default::default(),
+    })
+}
+
+fn error(code: i64, message: String) -> JsonrpcErrorError {
+    JsonrpcErrorError {
+        code,
+        data: None,
+        message,
+        extra: Default::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{StreamExt as _, channel::mpsc};
+    use mmcp_protocol::mcp::{
+        CallToolRequest, CallToolResultContent, CreateMessageResultContent, Role,
+        SamplingMessageContent, TextContent, ToolAnnotations,
+    };
+    use mmcp_rpc::RPCRuntime;
+    use mmcp_server::{
+        MCPServer,
+        primitives::tool::{IntoToolResult as _, Tool, ToolContext},
+    };
+
+    use super::*;
+
+    struct Echo;
+
+    impl Tool for Echo {
+        fn name(&self) -> std::borrow::Cow<'static, str> {
+            "echo".into()
+        }
+
+        fn description(&self) -> std::borrow::Cow<'static, str> {
+            "Echo the text".into()
+        }
+
+        fn input_schema(&self) -> std::borrow::Cow<'static, str> {
+            r#"{"type": "object"}"#.into()
+        }
+
+        fn annotations(&self) -> ToolAnnotations {
+            Default::default()
+        }
+
+        fn execute(
+            &self,
+            request: CallToolRequest,
+            _context: ToolContext,
+        ) -> std::pin::Pin<Box<dyn Future<Output = CallToolResult> + Send + '_>> {
+            Box::pin(async move {
+                let text = request.params.arguments.unwrap()["text"]
+                    .as_str()
+                    .unwrap()
+                    .to_string();
+                CallToolResult {
+                    content: vec![CallToolResultContent::TextContent(TextContent {
+                        annotations: None,
+                        text,
+                        r#type: Default::default(),
+                        extra: Default::default(),
+                    })],
+                    is_error: None,
+                    meta: None,
+                    extra: Default::default(),
+                }
+            })
+        }
+    }
+
+    /// Ask the client's LLM about the first root of the client.
+    struct Ask;
+
+    impl Tool for Ask {
+        fn name(&self) -> std::borrow::Cow<'static, str> {
+            "ask".into()
+        }
+
+        fn description(&self) -> std::borrow::Cow<'static, str> {
+            "Ask about the first root".into()
+        }
+
+        fn input_schema(&self) -> std::borrow::Cow<'static, str> {
+            r#"{"type": "object"}"#.into()
+        }
+
+        fn annotations(&self) -> ToolAnnotations {
+            Default::default()
+        }
+
+        fn execute(
+            &self,
+            _request: CallToolRequest,
+            context: ToolContext,
+        ) -> std::pin::Pin<Box<dyn Future<Output = CallToolResult> + Send + '_>> {
+            Box::pin(async move {
+                context.session().refresh_roots().await.unwrap();
+                let root = context.roots()[0].uri.clone();
+                match context.sample().user(root).send().await {
+                    Ok(result) => match result.content {
+                        CreateMessageResultContent::TextContent(text) => text.text,
+                        _ => "unexpected content".into(),
+                    },
+                    Err(e) => e.to_string(),
+                }
+                .into_tool_result()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client() {
+        let (client_tx, server_rx) = mpsc::channel(100);
+        let (server_tx, client_rx) = mpsc::channel(100);
+        let server = MCPServer::new("test", "0.1.0")
+            .with_instructions("Echo anything")
+            .add_tool(Echo);
+        tokio::spawn(server.start(RPCRuntime::new(server_tx, server_rx.map(Ok))));
+
+        let client = MCPClient::builder("test-client", "0.1.0")
+            .connect(RPCRuntime::new(client_tx, client_rx.map(Ok)))
+            .await
+            .unwrap();
+        assert_eq!(client.server_info().name, "test");
+        assert_eq!(client.instructions(), Some("Echo anything"));
+        assert!(client.server_capabilities().tools.is_some());
+
+        client.ping().await.unwrap();
+        let tools = client.list_tools(None).await.unwrap();
+        assert_eq!(tools.tools[0].name, "echo");
+        let result = client
+            .call_tool("echo", serde_json::json!({"text": "hello"}))
+            .await
+            .unwrap();
+        assert_eq!(
+            result.content,
+            vec![CallToolResultContent::TextContent(TextContent {
+                annotations: None,
+                text: "hello".into(),
+                r#type: Default::default(),
+                extra: Default::default(),
+            })]
+        );
+
+        let error = client
+            .read_resource("file:///unknown")
+            .await
+            .unwrap_err()
+            .downcast::<ServerError>()
+            .unwrap();
+        assert_eq!(error.code, error_codes::RESOURCE_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_server_requests() {
+        let (client_tx, server_rx) = mpsc::channel(100);
+        let (server_tx, client_rx) = mpsc::channel(100);
+        let server = MCPServer::new("test", "0.1.0").add_tool(Ask);
+        tokio::spawn(server.start(RPCRuntime::new(server_tx, server_rx.map(Ok))));
+
+        let client = MCPClient::builder("test-client", "0.1.0")
+            .on_list_roots(|| async {
+                vec![Root {
+                    name: None,
+                    uri: "file:///project".into(),
+                    extra: Default::default(),
+                }]
+            })
+            .on_sampling(|params| async move {
+                let SamplingMessageContent::TextContent(text) = &params.messages[0].content else {
+                    panic!("expected text content");
+                };
+                Ok(CreateMessageResult {
+                    content: CreateMessageResultContent::TextContent(TextContent {
+                        annotations: None,
+                        text: format!("{} is a Rust project", text.text),
+                        r#type: Default::default(),
+                        extra: Default::default(),
+                    }),
+                    meta: None,
+                    model: "test-model".into(),
+                    role: Role::Assistant,
+                    stop_reason: None,
+                    extra: Default::default(),
+                })
+            })
+            .connect(RPCRuntime::new(client_tx, client_rx.map(Ok)))
+            .await
+            .unwrap();
+
+        let result = client
+            .call_tool("ask", serde_json::Value::Null)
+            .await
+            .unwrap();
+        assert_eq!(
+            result.content,
+            vec![CallToolResultContent::TextContent(TextContent {
+                annotations: None,
+                text: "file:///project is a Rust project".into(),
+                r#type: Default::default(),
+                extra: Default::default(),
+            })]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_without_handler() {
+        let (client_tx, _server_rx) = mpsc::channel(100);
+        let (_server_tx, client_rx) = mpsc::channel::<JSONRPCMessage>(100);
+        let result = MCPClient::builder("test-client", "0.1.0")
+            .with_capabilities(ClientCapabilities {
+                sampling: Some(Default::default()),
+                ..Default::default()
+            })
+            .connect(RPCRuntime::new(client_tx, client_rx.map(Ok)))
+            .await;
+        assert!(result.is_err());
+    }
+}
@@ -1,13 +1,24 @@
 use darling::{FromMeta as _, ast::NestedMeta};
 use proc_macro::TokenStream;
 
+mod prompt;
+mod resource;
 mod tool;
+mod utils;
 
 /// Register a function as a tool.
 ///
 /// - For input types, [serde::Deserialize] and [schemars::JsonSchema] are required.
 /// - For the output type, [mmcp::server::primitives::tool::IntoToolResult] is required.
+/// - A `Json<T>` or `Result<Json<T>, E>` output also declares the schema of `T` as the
+///   `outputSchema` of the tool if it describes an object, which requires [schemars::JsonSchema]
+///   for `T`.
 /// - You must provide one of `description` argument or doc comments to the tool.
+/// - A parameter of type `&ToolContext` receives the context of the request, for reporting
+///   progress, logging and cancellation. It is not part of the input schema.
+/// - A parameter of type `State<S>` receives the state added with `MCPServer::with_state`, and
+///   may be destructured like `State(pool): State<Pool>`. It is not part of the input schema.
+///   If no such state was added, the call is answered with an error result.
 ///
 /// # Example
 ///
@@ -18,6 +29,19 @@ mod tool;
 ///     "Hello, world!".to_string()
 /// }
 ///
+/// /// Download a file, reporting progress.
+/// #[tool]
+/// async fn download(url: String, ctx: &ToolContext) -> String {
+///     ctx.report_progress(0.0, Some(1.0)).await.ok();
+///     fetch(&url).await
+/// }
+///
+/// /// Look up a user in the database.
+/// #[tool]
+/// async fn find_user(State(pool): State<PgPool>, id: i64) -> Json<User> {
+///     Json(query_user(&pool, id).await)
+/// }
+///
 /// #[tool(description = "Description of the tool.")]
 /// fn my_tool() -> String {
 ///     "Hello, world!".to_string()
@@ -41,3 +65,91 @@ pub fn tool(args: TokenStream, input: TokenStream) -> TokenStream {
 
     tool::generate(args, input).into()
 }
+
+/// Register a function as a resource.
+///
+/// - `uri` is required. If it is an RFC 6570 URI template such as `db://{table}/{id}`, the
+///   function is registered as a resource template and each argument is bound to the URI variable
+///   of the same name. Arguments must implement [serde::Deserialize]. A malformed template is a
+///   compile error.
+/// - Suggest values for a URI variable with `#[arg(complete = my_fn)]`, where `my_fn` is an
+///   `async fn(String) -> impl IntoCompletion` receiving the partial value. URI variables have no
+///   description, so `#[arg(description = ...)]` is a compile error.
+/// - For the output type, [mmcp::server::primitives::resource::IntoResourceContents] is required.
+/// - `name` defaults to the function name, and `description` defaults to the doc comments.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// /// The application configuration.
+/// #[resource(uri = "config://app", mime_type = "application/toml")]
+/// fn app_config() -> String {
+///     std::fs::read_to_string("app.toml").unwrap()
+/// }
+///
+/// /// A row of a table.
+/// #[resource(uri = "db://{table}/{id}", mime_type = "application/json")]
+/// async fn row(#[arg(complete = complete_table)] table: String, id: u64) -> Json<Row> {
+///     Json(fetch_row(&table, id).await)
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn resource(args: TokenStream, input: TokenStream) -> TokenStream {
+    let attr_args = match NestedMeta::parse_meta_list(args.into()) {
+        Ok(v) => v,
+        Err(e) => {
+            return TokenStream::from(darling::Error::from(e).write_errors());
+        }
+    };
+    let args = match resource::ResourceArgs::from_list(&attr_args) {
+        Ok(v) => v,
+        Err(e) => {
+            return TokenStream::from(e.write_errors());
+        }
+    };
+    let input = syn::parse_macro_input!(input as syn::ItemFn);
+
+    resource::generate(args, input).into()
+}
+
+/// Register a function as a prompt.
+///
+/// - Arguments must implement [serde::Deserialize] and [schemars::JsonSchema]. Clients always send
+///   prompt arguments as strings, which are parsed into the argument types.
+/// - Describe an argument with `#[arg(description = "...")]`, and suggest values for it with
+///   `#[arg(complete = my_fn)]`, where `my_fn` is an `async fn(String) -> impl IntoCompletion`
+///   receiving the partial value.
+/// - For the output type, [mmcp::server::primitives::prompt::IntoPromptMessages] is required.
+/// - `name` defaults to the function name, and `description` defaults to the doc comments.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// /// Review a piece of code.
+/// #[prompt]
+/// fn code_review(
+///     #[arg(description = "The code to review")] code: String,
+///     #[arg(description = "The language of the code", complete = complete_language)]
+///     language: Option<String>,
+/// ) -> Vec<User<String>> {
+///     vec![User(format!("Please review this {} code:\n{code}", language.unwrap_or_default()))]
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn prompt(args: TokenStream, input: TokenStream) -> TokenStream {
+    let attr_args = match NestedMeta::parse_meta_list(args.into()) {
+        Ok(v) => v,
+        Err(e) => {
+            return TokenStream::from(darling::Error::from(e).write_errors());
+        }
+    };
+    let args = match prompt::PromptArgs::from_list(&attr_args) {
+        Ok(v) => v,
+        Err(e) => {
+            return TokenStream::from(e.write_errors());
+        }
+    };
+    let input = syn::parse_macro_input!(input as syn::ItemFn);
+
+    prompt::generate(args, input).into()
+}
@@ -0,0 +1,167 @@
+use convert_case::{Case, Casing as _};
+use darling::FromMeta;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{ItemFn, ReturnType, parse_quote};
+
+use crate::utils::{completer_method, doc_comment, fn_fields, take_arg_options};
+
+#[derive(FromMeta)]
+pub struct PromptArgs {
+    name: Option<String>,
+    description: Option<String>,
+}
+
+pub fn generate(args: PromptArgs, mut item: ItemFn) -> TokenStream {
+    let arg_options = match take_arg_options(&mut item) {
+        Ok(arg_options) => arg_options,
+        Err(e) => return e,
+    };
+
+    let fn_name = &item.sig.ident;
+    let prompt_struct_name = format_ident!("{}Prompt", fn_name.to_string().to_case(Case::Pascal));
+    let arguments_struct_name = format_ident!(
+        "{}PromptArguments",
+        fn_name.to_string().to_case(Case::Pascal)
+    );
+
+    let name = args.name.unwrap_or_else(|| fn_name.to_string());
+    let description = match args.description {
+        Some(description) => Some(description),
+        None => Some(doc_comment(&item.attrs)).filter(|doc| !doc.is_empty()),
+    };
+    let description = match description {
+        Some(description) => quote!(Some(#description.into())),
+        None => quote!(None),
+    };
+
+    let output_type = match &item.sig.output {
+        ReturnType::Type(_arrow, ty) => ty.clone(),
+        ReturnType::Default => {
+            parse_quote!(())
+        }
+    };
+
+    let fields = match fn_fields(&item) {
+        Ok(fields) => fields,
+        Err(e) => return e,
+    };
+    let field_names = fields.iter().map(|field| field.ident).collect::<Vec<_>>();
+    let field_docs = arg_options
+        .iter()
+        .map(|options| match &options.description {
+            Some(description) => quote!(#[doc = #description]),
+            None => quote!(),
+        });
+
+    let completer = completer_method(&fields, &arg_options);
+
+    let call = if item.sig.asyncness.is_some() {
+        quote!(#fn_name(#(#field_names),*).await)
+    } else {
+        quote!(#fn_name(#(#field_names),*))
+    };
+
+    let get_arguments = if fields.is_empty() {
+        quote!(Vec::new())
+    } else {
+        quote! {
+            ::mmcp::server::primitives::prompt::arguments_from_schema(
+                &::mmcp::serde_json::to_value(::mmcp::schemars::schema_for!(#arguments_struct_name))
+                    .expect("Failed to serialize schema with serde_json"),
+            )
+        }
+    };
+
+    quote! {
+        #item
+
+        #[derive(Debug, Clone, PartialEq, ::mmcp::serde::Serialize, ::mmcp::serde::Deserialize, ::mmcp::schemars::JsonSchema)]
+        #[serde(crate = "::mmcp::serde")]
+        #[schemars(crate = "::mmcp::schemars")]
+        pub struct #arguments_struct_name {
+            #(#field_docs #fields,)*
+        }
+
+        #[derive(Default)]
+        pub struct #prompt_struct_name;
+
+        ::mmcp::server::inventory::submit! { ::mmcp::server::inventory::PromptRegistration::new::<#prompt_struct_name>() }
+
+        impl ::mmcp::server::primitives::prompt::TypedPrompt for #prompt_struct_name {
+            type Arguments = #arguments_struct_name;
+            type Output = #output_type;
+
+            fn name(&self) -> std::borrow::Cow<'static, str> {
+                #name.into()
+            }
+
+            fn description(&self) -> Option<std::borrow::Cow<'static, str>> {
+                #description
+            }
+
+            fn arguments(&self) -> Vec<::mmcp::protocol::mcp::PromptArgument> {
+                #get_arguments
+            }
+
+            #completer
+
+            async fn get(&self, arguments: Self::Arguments) -> Self::Output {
+                let #arguments_struct_name { #(#field_names,)* } = arguments;
+                #call
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use darling::ast::NestedMeta;
+
+    use super::*;
+
+    fn expand(args: TokenStream, item: ItemFn) -> String {
+        let args = PromptArgs::from_list(&NestedMeta::parse_meta_list(args).unwrap()).unwrap();
+        generate(args, item).to_string()
+    }
+
+    #[test]
+    fn test_prompt() {
+        let expanded = expand(
+            quote!(),
+            parse_quote! {
+                /// Explain an expression
+                fn explain(
+                    #[arg(description = "The expression", complete = complete_expression)]
+                    expression: String,
+                ) -> String {
+                    expression
+                }
+            },
+        );
+        assert!(expanded.contains("pub struct ExplainPromptArguments"));
+        assert!(expanded.contains("TypedPrompt for ExplainPrompt"));
+        assert!(expanded.contains(r#"# [doc = "The expression"] expression : String"#));
+        assert!(expanded.contains("arguments_from_schema"));
+        assert!(expanded.contains(r#""expression" => Some (& complete_expression)"#));
+        assert!(expanded.contains(r#"Some ("Explain an expression" . into ())"#));
+        // The `#[arg]` attributes are removed from the function
+        assert!(!expanded.contains("# [arg"));
+    }
+
+    #[test]
+    fn test_prompt_without_arguments() {
+        let expanded = expand(
+            quote!(name = "greeting", description = "Say hello"),
+            parse_quote! {
+                fn hello() -> &'static str {
+                    "Hello"
+                }
+            },
+        );
+        assert!(expanded.contains(r#""greeting" . into ()"#));
+        assert!(expanded.contains(r#"Some ("Say hello" . into ())"#));
+        assert!(!expanded.contains("arguments_from_schema"));
+        assert!(!expanded.contains("fn completer"));
+    }
+}
@@ -0,0 +1,272 @@
+use convert_case::{Case, Casing as _};
+use darling::FromMeta;
+use mmcp_uri_template::UriTemplate;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{ItemFn, ReturnType, parse_quote};
+
+use crate::utils::{completer_method, doc_comment, fn_fields, take_arg_options};
+
+#[derive(FromMeta)]
+pub struct ResourceArgs {
+    uri: String,
+    name: Option<String>,
+    description: Option<String>,
+    mime_type: Option<String>,
+}
+
+pub fn generate(args: ResourceArgs, mut item: ItemFn) -> TokenStream {
+    let arg_options = match take_arg_options(&mut item) {
+        Ok(arg_options) => arg_options,
+        Err(e) => return e,
+    };
+
+    let fn_name = &item.sig.ident;
+    let resource_struct_name =
+        format_ident!("{}Resource", fn_name.to_string().to_case(Case::Pascal));
+
+    let uri = &args.uri;
+    let name = args.name.unwrap_or_else(|| fn_name.to_string());
+    let description = match args.description {
+        Some(description) => Some(description),
+        None => Some(doc_comment(&item.attrs)).filter(|doc| !doc.is_empty()),
+    };
+    let description = match description {
+        Some(description) => quote!(Some(#description.into())),
+        None => quote!(None),
+    };
+    let mime_type = match &args.mime_type {
+        Some(mime_type) => quote!(Some(#mime_type.into())),
+        None => quote!(None),
+    };
+
+    let output_type = match &item.sig.output {
+        ReturnType::Type(_arrow, ty) => ty.clone(),
+        ReturnType::Default => {
+            parse_quote!(())
+        }
+    };
+
+    let fields = match fn_fields(&item) {
+        Ok(fields) => fields,
+        Err(e) => return e,
+    };
+    let field_names = fields.iter().map(|field| field.ident).collect::<Vec<_>>();
+
+    let call = if item.sig.asyncness.is_some() {
+        quote!(#fn_name(#(#field_names),*).await)
+    } else {
+        quote!(#fn_name(#(#field_names),*))
+    };
+
+    let template = match UriTemplate::parse(uri) {
+        Ok(template) => template,
+        Err(e) => {
+            let message = e.to_string();
+            return quote! {
+                compile_error!(#message);
+            };
+        }
+    };
+    // URI variables have no description, unlike prompt arguments
+    if arg_options
+        .iter()
+        .any(|options| options.description.is_some())
+    {
+        return quote! {
+            compile_error!("`#[arg(description = ...)]` is not supported on resources. Only `#[arg(complete = ...)]` applies to URI variables.");
+        };
+    }
+
+    let template_variables = template.variables().collect::<Vec<_>>();
+    if template_variables.is_empty() {
+        if !fields.is_empty() {
+            return quote! {
+                compile_error!("A resource with a plain URI cannot take arguments. Use a URI template such as `scheme://{name}` to bind URI variables to the arguments.");
+            };
+        }
+
+        return quote! {
+            #item
+
+            #[derive(Default)]
+            pub struct #resource_struct_name;
+
+            ::mmcp::server::inventory::submit! { ::mmcp::server::inventory::ResourceRegistration::new::<#resource_struct_name>() }
+
+            impl ::mmcp::server::primitives::resource::TypedResource for #resource_struct_name {
+                type Output = #output_type;
+
+                fn uri(&self) -> std::borrow::Cow<'static, str> {
+                    #uri.into()
+                }
+
+                fn name(&self) -> std::borrow::Cow<'static, str> {
+                    #name.into()
+                }
+
+                fn description(&self) -> Option<std::borrow::Cow<'static, str>> {
+                    #description
+                }
+
+                fn mime_type(&self) -> Option<std::borrow::Cow<'static, str>> {
+                    #mime_type
+                }
+
+                fn annotations(&self) -> Option<::mmcp::protocol::mcp::Annotations> {
+                    None
+                }
+
+                async fn read(&self) -> Self::Output {
+                    #call
+                }
+            }
+        };
+    }
+
+    for field in &fields {
+        if !template_variables.contains(&field.ident.to_string().as_str()) {
+            let message = format!(
+                "Argument `{}` does not appear in the URI template `{}`",
+                field.ident, uri
+            );
+            return quote! {
+                compile_error!(#message);
+            };
+        }
+    }
+
+    let completer = completer_method(&fields, &arg_options);
+
+    let variables_struct_name = format_ident!(
+        "{}ResourceVariables",
+        fn_name.to_string().to_case(Case::Pascal)
+    );
+
+    quote! {
+        #item
+
+        #[derive(Debug, Clone, PartialEq, ::mmcp::serde::Deserialize)]
+        #[serde(crate = "::mmcp::serde")]
+        pub struct #variables_struct_name {
+            #(#fields,)*
+        }
+
+        #[derive(Default)]
+        pub struct #resource_struct_name;
+
+        ::mmcp::server::inventory::submit! { ::mmcp::server::inventory::ResourceTemplateRegistration::new::<#resource_struct_name>() }
+
+        impl ::mmcp::server::primitives::resource::TypedResourceTemplate for #resource_struct_name {
+            type Variables = #variables_struct_name;
+            type Output = #output_type;
+
+            fn uri_template(&self) -> std::borrow::Cow<'static, str> {
+                #uri.into()
+            }
+
+            fn name(&self) -> std::borrow::Cow<'static, str> {
+                #name.into()
+            }
+
+            fn description(&self) -> Option<std::borrow::Cow<'static, str>> {
+                #description
+            }
+
+            fn mime_type(&self) -> Option<std::borrow::Cow<'static, str>> {
+                #mime_type
+            }
+
+            fn annotations(&self) -> Option<::mmcp::protocol::mcp::Annotations> {
+                None
+            }
+
+            #completer
+
+            async fn read(&self, variables: Self::Variables) -> Self::Output {
+                let #variables_struct_name { #(#field_names,)* } = variables;
+                #call
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use darling::ast::NestedMeta;
+
+    use super::*;
+
+    fn expand(args: TokenStream, item: ItemFn) -> String {
+        let args = ResourceArgs::from_list(&NestedMeta::parse_meta_list(args).unwrap()).unwrap();
+        generate(args, item).to_string()
+    }
+
+    #[test]
+    fn test_resource() {
+        let expanded = expand(
+            quote!(uri = "config://app"),
+            parse_quote! {
+                fn app_config() -> String {
+                    String::new()
+                }
+            },
+        );
+        assert!(expanded.contains("TypedResource for AppConfigResource"));
+        assert!(!expanded.contains("compile_error"));
+    }
+
+    #[test]
+    fn test_resource_template() {
+        let expanded = expand(
+            quote!(uri = "db://{table}{/id}"),
+            parse_quote! {
+                fn row(#[arg(complete = complete_table)] table: String, id: u64) -> String {
+                    format!("{table}/{id}")
+                }
+            },
+        );
+        assert!(expanded.contains("TypedResourceTemplate for RowResource"));
+        assert!(expanded.contains("complete_table"));
+        assert!(!expanded.contains("compile_error"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases: [(TokenStream, ItemFn, &str); 4] = [
+            (
+                quote!(uri = "db://{table"),
+                parse_quote!(
+                    fn row(table: String) {}
+                ),
+                "unclosed expression in URI template",
+            ),
+            (
+                quote!(uri = "config://app"),
+                parse_quote!(
+                    fn app_config(name: String) {}
+                ),
+                "A resource with a plain URI cannot take arguments",
+            ),
+            (
+                quote!(uri = "db://{table}"),
+                parse_quote!(
+                    fn row(table: String, id: u64) {}
+                ),
+                "Argument `id` does not appear in the URI template",
+            ),
+            (
+                quote!(uri = "db://{table}"),
+                parse_quote!(
+                    fn row(#[arg(description = "The table")] table: String) {}
+                ),
+                "is not supported on resources",
+            ),
+        ];
+        for (args, item, message) in cases {
+            let expanded = expand(args, item);
+            assert!(expanded.starts_with("compile_error !"), "{expanded}");
+            assert!(expanded.contains(message), "{expanded}");
+        }
+    }
+}
@@ -1,12 +1,14 @@
 use convert_case::{Case, Casing as _};
 use darling::FromMeta;
 use proc_macro2::TokenStream;
-use quote::{ToTokens, format_ident, quote};
+use quote::{format_ident, quote};
 use syn::{
-    Expr, ExprLit, FnArg, Ident, ItemFn, Lit, Meta, MetaNameValue, Pat, PatType, ReturnType, Token,
-    Type, parse_quote,
+    FnArg, GenericArgument, Ident, ItemFn, PatType, PathArguments, ReturnType, Type, TypePath,
+    TypeReference, parse_quote,
 };
 
+use crate::utils::{doc_comment, fn_fields};
+
 #[derive(FromMeta)]
 pub struct ToolArgs {
     description: Option<String>,
@@ -21,29 +23,7 @@ pub fn generate(args: ToolArgs, item: ItemFn) -> TokenStream {
         desc
     } else {
         // Only process doc comments if no description arg is provided
-        let doc_description = item
-            .attrs
-            .iter()
-            .filter_map(|attr| {
-                if !attr.path().is_ident("doc") {
-                    return None;
-                }
-                let Meta::NameValue(MetaNameValue {
-                    value:
-                        Expr::Lit(ExprLit {
-                            lit: Lit::Str(doc), ..
-                        }),
-                    ..
-                }) = &attr.meta
-                else {
-                    panic!("Expected a doc attribute but got {:?}", attr);
-                };
-                Some(doc.value())
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
-            .trim()
-            .to_string();
+        let doc_description = doc_comment(&item.attrs);
 
         if doc_description.is_empty() {
             return quote! {
@@ -72,12 +52,51 @@ pub fn generate(args: ToolArgs, item: ItemFn) -> TokenStream {
         }
     };
 
+    // `Json<T>` results are also sent as `structuredContent`, described by the schema of `T`. The
+    // specification requires an object schema, and other values are not sent as structured
+    // content, so the schema is only declared if it is one.
+    let output_schema = match json_output(&output_type) {
+        Some(ty) => quote! {
+            fn output_schema(&self) -> Option<std::borrow::Cow<'static, str>> {
+                let schema = ::mmcp::serde_json::to_value(&::mmcp::schemars::schema_for!(#ty)).expect("Failed to serialize schema with serde_json");
+                (schema["type"] == "object").then(|| schema.to_string().into())
+            }
+        },
+        None => quote!(),
+    };
+
     let call = if item.sig.asyncness.is_some() {
         quote!(#call.await)
     } else {
         quote!(#call)
     };
 
+    let context = if item
+        .sig
+        .inputs
+        .iter()
+        .any(|input| matches!(input, FnArg::Typed(PatType { ty, .. }) if is_injected(ty)))
+    {
+        quote!(context)
+    } else {
+        quote!(_context)
+    };
+
+    // A missing state is answered as an error result instead of calling the fn
+    let (output_type, call) = if item
+        .sig
+        .inputs
+        .iter()
+        .any(|input| matches!(input, FnArg::Typed(PatType { ty, .. }) if is_state(ty)))
+    {
+        (
+            quote!(::std::result::Result<#output_type, ::mmcp::server::primitives::tool::MissingState>),
+            quote!(Ok(#call)),
+        )
+    } else {
+        (quote!(#output_type), call)
+    };
+
     quote! {
         #item
         #input_struct_def
@@ -103,11 +122,17 @@ pub fn generate(args: ToolArgs, item: ItemFn) -> TokenStream {
                 #get_schema.into()
             }
 
+            #output_schema
+
             fn annotations(&self) -> ::mmcp::protocol::mcp::ToolAnnotations {
                 ::mmcp::protocol::mcp::ToolAnnotations::default()
             }
 
-            async fn execute(&self, arguments: Self::Input) -> Self::Output {
+            async fn execute(
+                &self,
+                arguments: Self::Input,
+                #context: ::mmcp::server::primitives::tool::ToolContext,
+            ) -> Self::Output {
                 #destructure_input
                 #call
             }
@@ -128,27 +153,37 @@ struct InputSchema {
     call: TokenStream,
 }
 
-pub struct Field<'a> {
-    ident: &'a Ident,
-    colon_token: &'a Token![:],
-    ty: &'a Type,
-}
+fn generate_input_schema(tool_name: &Ident, input: &ItemFn) -> Result<InputSchema, TokenStream> {
+    // The context and states are resolved at call time and are not part of the input, so their
+    // parameters may use any pattern, like `State(pool): State<Pool>`.
+    let mut input_fn = input.clone();
+    input_fn.sig.inputs = input
+        .sig
+        .inputs
+        .iter()
+        .filter(|input| !matches!(input, FnArg::Typed(PatType { ty, .. }) if is_injected(ty)))
+        .cloned()
+        .collect();
+    let fields = fn_fields(&input_fn)?;
 
-impl ToTokens for Field<'_> {
-    fn to_tokens(&self, tokens: &mut TokenStream) {
-        let Self {
-            ident,
-            colon_token,
-            ty,
-        } = self;
-        tokens.extend(quote! {
-            #ident #colon_token #ty
-        });
-    }
-}
+    let mut field_idents = fields.iter().map(|field| field.ident);
+    let args = input.sig.inputs.iter().map(|input| match input {
+        FnArg::Typed(PatType { ty, .. }) if is_tool_context(ty) => quote!(&context),
+        FnArg::Typed(PatType { ty, .. }) if is_state(ty) => {
+            quote!(::mmcp::server::primitives::tool::State::from_context(
+                &context
+            )?)
+        }
+        _ => {
+            let ident = field_idents.next();
+            quote!(#ident)
+        }
+    });
+    let call = quote! {
+        #tool_name(#(#args),*)
+    };
 
-fn generate_input_schema(tool_name: &Ident, input: &ItemFn) -> Result<InputSchema, TokenStream> {
-    if input.sig.inputs.is_empty() {
+    if fields.is_empty() {
         return Ok(InputSchema {
             struct_def: TokenStream::new(),
             struct_type: parse_quote!(::mmcp::schemars::Any),
@@ -156,44 +191,12 @@ fn generate_input_schema(tool_name: &Ident, input: &ItemFn) -> Result<InputSchem
                 r#"{"type": "object"}"#
             },
             destructure_input: quote! {},
-            call: quote! {
-                #tool_name()
-            },
+            call,
         });
     }
 
     let struct_name = format_ident!("{}InputSchema", tool_name.to_string().to_case(Case::Pascal));
 
-    let fields = input
-        .sig
-        .inputs
-        .iter()
-        .map(|input| {
-            let FnArg::Typed(PatType {
-                pat,
-                colon_token,
-                ty,
-                ..
-            }) = input
-            else {
-                return Err(quote! {
-                    compile_error!("Expected a typed argument but got {}", stringify!(#input))
-                });
-            };
-            let Pat::Ident(ident) = &**pat else {
-                return Err(quote! {
-                    compile_error!("Expected an ident pattern but got {}", stringify!(#input))
-                });
-            };
-
-            Ok(Field {
-                ident: &ident.ident,
-                colon_token,
-                ty,
-            })
-        })
-        .collect::<Result<Vec<_>, TokenStream>>()?;
-
     let field_names = fields.iter().map(|field| &field.ident).collect::<Vec<_>>();
 
     let struct_def = quote! {
@@ -213,10 +216,6 @@ fn generate_input_schema(tool_name: &Ident, input: &ItemFn) -> Result<InputSchem
         let #struct_name { #(#field_names,)* } = arguments;
     };
 
-    let call = quote! {
-        #tool_name(#(#field_names),*)
-    };
-
     Ok(InputSchema {
         struct_def,
         struct_type: parse_quote!(#struct_name),
@@ -225,3 +224,59 @@ fn generate_input_schema(tool_name: &Ident, input: &ItemFn) -> Result<InputSchem
         call,
     })
 }
+
+/// The `T` of a `Json<T>` or `Result<Json<T>, E>` return type.
+fn json_output(ty: &Type) -> Option<&Type> {
+    let (ident, ty) = single_generic(ty)?;
+    match ident.to_string().as_str() {
+        "Json" => Some(ty),
+        "Result" => match single_generic(ty)? {
+            (ident, ty) if ident == "Json" => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The name of a path type and its first generic type argument, like `Json` and `T` of `Json<T>`.
+fn single_generic(ty: &Type) -> Option<(&Ident, &Type)> {
+    let Type::Path(TypePath { path, .. }) = ty else {
+        return None;
+    };
+    let segment = path.segments.last()?;
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    match arguments.args.first()? {
+        GenericArgument::Type(ty) => Some((&segment.ident, ty)),
+        _ => None,
+    }
+}
+
+/// Whether the parameter is resolved from the context of the call instead of the input.
+fn is_injected(ty: &Type) -> bool {
+    is_tool_context(ty) || is_state(ty)
+}
+
+/// Whether the type of a parameter is `State<S>`.
+fn is_state(ty: &Type) -> bool {
+    let Type::Path(TypePath { path, .. }) = ty else {
+        return false;
+    };
+    path.segments.last().is_some_and(|segment| {
+        segment.ident == "State" && matches!(segment.arguments, PathArguments::AngleBracketed(_))
+    })
+}
+
+/// Whether the type of a parameter is `&ToolContext`.
+fn is_tool_context(ty: &Type) -> bool {
+    let Type::Reference(TypeReference { elem, .. }) = ty else {
+        return false;
+    };
+    let Type::Path(TypePath { path, .. }) = &**elem else {
+        return false;
+    };
+    path.segments
+        .last()
+        .is_some_and(|segment| segment.ident == "ToolContext")
+}
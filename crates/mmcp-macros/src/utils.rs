@@ -0,0 +1,152 @@
+use darling::FromMeta;
+use proc_macro2::TokenStream;
+use quote::{ToTokens, quote};
+use syn::{
+    Attribute, Expr, ExprLit, FnArg, Ident, ItemFn, Lit, Meta, MetaNameValue, Pat, PatType, Path,
+    Token, Type,
+};
+
+/// Collect the doc comments of an item into a single trimmed string.
+pub fn doc_comment(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter_map(|attr| {
+            if !attr.path().is_ident("doc") {
+                return None;
+            }
+            let Meta::NameValue(MetaNameValue {
+                value:
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(doc), ..
+                    }),
+                ..
+            }) = &attr.meta
+            else {
+                panic!("Expected a doc attribute but got {:?}", attr);
+            };
+            Some(doc.value())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+pub struct Field<'a> {
+    pub ident: &'a Ident,
+    pub colon_token: &'a Token![:],
+    pub ty: &'a Type,
+}
+
+impl ToTokens for Field<'_> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let Self {
+            ident,
+            colon_token,
+            ty,
+        } = self;
+        tokens.extend(quote! {
+            #ident #colon_token #ty
+        });
+    }
+}
+
+/// Get the arguments of the function as fields.
+pub fn fn_fields(input: &ItemFn) -> Result<Vec<Field<'_>>, TokenStream> {
+    input
+        .sig
+        .inputs
+        .iter()
+        .map(|input| {
+            let FnArg::Typed(PatType {
+                pat,
+                colon_token,
+                ty,
+                ..
+            }) = input
+            else {
+                return Err(quote! {
+                    compile_error!("Expected a typed argument but got {}", stringify!(#input))
+                });
+            };
+            let Pat::Ident(ident) = &**pat else {
+                return Err(quote! {
+                    compile_error!("Expected an ident pattern but got {}", stringify!(#input))
+                });
+            };
+
+            Ok(Field {
+                ident: &ident.ident,
+                colon_token,
+                ty,
+            })
+        })
+        .collect()
+}
+
+/// Options of an argument given with `#[arg(...)]`.
+#[derive(Default, FromMeta)]
+pub struct ArgOptions {
+    pub description: Option<String>,
+    /// An `async fn(String) -> impl IntoCompletion` suggesting values for the argument.
+    pub complete: Option<Path>,
+}
+
+/// Remove the `#[arg(...)]` attributes from the arguments of the function, returning the parsed
+/// options for each argument in order.
+pub fn take_arg_options(item: &mut ItemFn) -> Result<Vec<ArgOptions>, TokenStream> {
+    item.sig
+        .inputs
+        .iter_mut()
+        .map(|input| {
+            let FnArg::Typed(PatType { attrs, .. }) = input else {
+                return Ok(ArgOptions::default());
+            };
+            let mut options = ArgOptions::default();
+            let mut error = None;
+            attrs.retain(|attr| {
+                if !attr.path().is_ident("arg") {
+                    return true;
+                }
+                match ArgOptions::from_meta(&attr.meta) {
+                    Ok(parsed) => options = parsed,
+                    Err(e) => error = Some(e.write_errors()),
+                }
+                false
+            });
+            match error {
+                Some(error) => Err(error),
+                None => Ok(options),
+            }
+        })
+        .collect()
+}
+
+/// Generate the `completer` method dispatching to the `#[arg(complete = ...)]` functions, or
+/// nothing if no argument has one.
+pub fn completer_method(fields: &[Field<'_>], arg_options: &[ArgOptions]) -> TokenStream {
+    let arms = fields
+        .iter()
+        .zip(arg_options)
+        .filter_map(|(field, options)| {
+            let complete = options.complete.as_ref()?;
+            let name = field.ident.to_string();
+            Some(quote!(#name => Some(&#complete)))
+        })
+        .collect::<Vec<_>>();
+    if arms.is_empty() {
+        return quote!();
+    }
+
+    quote! {
+        fn completer(
+            &self,
+            argument: &str,
+        ) -> Option<&(dyn ::mmcp::server::primitives::completion::Completer + Send + Sync)> {
+            match argument {
+                #(#arms,)*
+                _ => None,
+            }
+        }
+    }
+}
@@ -11,4 +11,8 @@ pub mod error_codes {
     pub const INVALID_PARAMS: i64 = -32602;
     /// Internal JSON-RPC error.
     pub const INTERNAL_ERROR: i64 = -32603;
+
+    // MCP specific error codes
+    /// The requested resource does not exist.
+    pub const RESOURCE_NOT_FOUND: i64 = -32002;
 }
@@ -3,11 +3,29 @@ use std::str::FromStr;
 pub mod consts;
 pub mod mcp;
 pub mod port;
+pub use mmcp_uri_template as uri_template;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ProtocolVersion {
     V20241105,
     V20250326,
+    /// Only structured tool results are implemented. It is not in [`ProtocolVersion::ALL`], so
+    /// servers negotiate it only when their version policy opts in.
+    V20250618,
+}
+
+impl ProtocolVersion {
+    /// The latest version fully implemented by this crate
+    pub const LATEST: Self = Self::V20250326;
+
+    /// All the versions fully implemented by this crate, from the oldest
+    pub const ALL: &[Self] = &[Self::V20241105, Self::V20250326];
+
+    /// Whether tools may declare an `outputSchema` and return `structuredContent`, which were
+    /// added in 2025-06-18.
+    pub fn supports_structured_content(self) -> bool {
+        self >= Self::V20250618
+    }
 }
 
 impl FromStr for ProtocolVersion {
@@ -17,6 +35,7 @@ impl FromStr for ProtocolVersion {
         Ok(match s {
             "2024-11-05" => Self::V20241105,
             "2025-03-26" => Self::V20250326,
+            "2025-06-18" => Self::V20250618,
             _ => return Err(anyhow::anyhow!("invalid protocol version: {}", s)),
         })
     }
@@ -27,6 +46,7 @@ impl std::fmt::Display for ProtocolVersion {
         match self {
             Self::V20241105 => write!(f, "2024-11-05"),
             Self::V20250326 => write!(f, "2025-03-26"),
+            Self::V20250618 => write!(f, "2025-06-18"),
         }
     }
 }
@@ -40,6 +60,7 @@ mod tests {
         // Test to_string
         assert_eq!(ProtocolVersion::V20241105.to_string(), "2024-11-05");
         assert_eq!(ProtocolVersion::V20250326.to_string(), "2025-03-26");
+        assert_eq!(ProtocolVersion::V20250618.to_string(), "2025-06-18");
 
         // Test FromStr
         assert_eq!(
@@ -50,6 +71,10 @@ mod tests {
             "2025-03-26".parse::<ProtocolVersion>().unwrap(),
             ProtocolVersion::V20250326
         );
+        assert_eq!(
+            "2025-06-18".parse::<ProtocolVersion>().unwrap(),
+            ProtocolVersion::V20250618
+        );
 
         // Test error case
         assert!("invalid".parse::<ProtocolVersion>().is_err());
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use serde::{Serialize, de::DeserializeOwned};
 
 use crate::mcp::{JSONRPCError, JSONRPCMessage, RequestId};
@@ -8,6 +10,57 @@ pub enum RPCPortError {
     Serialize(#[from] serde_json::Error),
     #[error("failed to serialize result to object got {0}")]
     SerializeNotObject(serde_json::Value),
+    #[error("request timed out after {0:?}")]
+    Timeout(Duration),
+}
+
+/// How long a request waits for its response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RequestTimeout {
+    /// Use the default timeout of the sink
+    #[default]
+    Default,
+    /// Wait at most this long
+    After(Duration),
+    /// Wait until the response arrives, however long it takes
+    Never,
+}
+
+/// Options for a single request sent with [`RPCSink::request_with_options`].
+#[derive(Debug, Clone)]
+pub struct RequestOptions {
+    /// How long to wait for the response.
+    pub timeout: RequestTimeout,
+    /// Whether to send `notifications/cancelled` to the peer when the request times out, or is
+    /// dropped before its response arrives.
+    pub cancel_on_timeout: bool,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self {
+            timeout: RequestTimeout::Default,
+            cancel_on_timeout: true,
+        }
+    }
+}
+
+impl RequestOptions {
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = RequestTimeout::After(timeout);
+        self
+    }
+
+    /// Wait for the response without any timeout, even if the sink has a default one.
+    pub fn no_timeout(mut self) -> Self {
+        self.timeout = RequestTimeout::Never;
+        self
+    }
+
+    pub fn cancel_on_timeout(mut self, cancel_on_timeout: bool) -> Self {
+        self.cancel_on_timeout = cancel_on_timeout;
+        self
+    }
 }
 
 pub trait RPCSink {
@@ -33,17 +86,32 @@ pub trait RPCSink {
     /// Send a request to the peer and wait for a response.
     fn request<T: Serialize + Send, R: DeserializeOwned + Send>(
         &mut self,
-        request_id: RequestId,
         method: &str,
         request: T,
+    ) -> impl Future<Output = anyhow::Result<Result<R, JSONRPCError>>> + Send {
+        self.request_with_options(method, request, RequestOptions::default())
+    }
+
+    /// Send a request to the peer and wait for a response, failing with
+    /// [`RPCPortError::Timeout`] if it does not arrive in time.
+    fn request_with_options<T: Serialize + Send, R: DeserializeOwned + Send>(
+        &mut self,
+        method: &str,
+        request: T,
+        options: RequestOptions,
     ) -> impl Future<Output = anyhow::Result<Result<R, JSONRPCError>>> + Send;
 }
 
 pub trait RPCPort {
     /// Get the sink for sending messages to the peer.
     fn sink(&self) -> impl RPCSink + Clone + Send + 'static;
-    /// Fetch a message from the peer. Handling commands in the background which sent by the `RPCSink`.
+    /// Fetch a message from the peer, passing responses to the requests sent by the `RPCSink`.
     fn progress(
         &mut self,
     ) -> impl std::future::Future<Output = anyhow::Result<Option<JSONRPCMessage>>> + Send;
+    /// Wait until the messages already sent through the sinks are delivered to the peer, and stop
+    /// sending more. Does nothing by default, for ports whose sinks deliver as they send.
+    fn close(&mut self) -> impl std::future::Future<Output = anyhow::Result<()>> + Send {
+        async { Ok(()) }
+    }
 }
@@ -1,53 +1,71 @@
-use std::collections::HashMap;
+mod peer;
 
-use futures::{
-    Sink, SinkExt, Stream, StreamExt,
-    channel::{mpsc, oneshot},
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicI64, Ordering},
+    },
+    time::Duration,
 };
+
+use futures::{Sink, SinkExt, Stream, StreamExt, channel::oneshot};
 use mmcp_protocol::{
     mcp::{
-        JSONRPCError, JSONRPCMessage, JSONRPCNotification, JSONRPCRequest, JSONRPCResponse,
-        JsonrpcBatchResponseItem, JsonrpcNotificationParams, JsonrpcRequestParams, RequestId,
-        Result as JsonRpcResult,
+        CancelledNotificationParams, JSONRPCError, JSONRPCMessage, JSONRPCNotification,
+        JSONRPCRequest, JSONRPCResponse, JsonrpcBatchResponseItem, JsonrpcNotificationParams,
+        JsonrpcRequestParams, RequestId, Result as JsonRpcResult,
     },
-    port::{RPCPort, RPCPortError, RPCSink},
+    port::{RPCPort, RPCPortError, RPCSink, RequestOptions, RequestTimeout},
 };
 use serde_json::Value;
 
+pub use peer::Peer;
+
+/// How long requests wait for their response unless
+/// [`RPCRuntime::with_request_timeout`] or [`RequestOptions::timeout`] says otherwise.
+/// [`RequestOptions::no_timeout`] waits without any timeout.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
 type ResponseSubscriber = oneshot::Sender<Result<JSONRPCResponse, JSONRPCError>>;
 
-enum Command {
-    WaitResponse {
-        request_id: RequestId,
-        response: ResponseSubscriber,
-    },
-}
+/// The requests waiting for their responses, shared by the runtime and its senders so that a
+/// request is registered before it is sent, even while the runtime is waiting for a message.
+type ResponseSubscriptions = Arc<Mutex<HashMap<RequestId, ResponseSubscriber>>>;
 
 #[derive(Clone)]
 pub struct RPCSender<T> {
     rpc_tx: T,
-    command_tx: mpsc::Sender<Command>,
+    response_subscriptions: ResponseSubscriptions,
+    next_request_id: Arc<AtomicI64>,
+    request_timeout: Option<Duration>,
 }
 
 pub struct RPCRuntime<T, R> {
     rpc_tx: T,
     rpc_rx: R,
-    command_tx: mpsc::Sender<Command>,
-    command_rx: mpsc::Receiver<Command>,
-    response_subscriptions: HashMap<RequestId, ResponseSubscriber>,
+    response_subscriptions: ResponseSubscriptions,
+    next_request_id: Arc<AtomicI64>,
+    request_timeout: Option<Duration>,
 }
 
 impl<T, R> RPCRuntime<T, R> {
     pub fn new(rpc_tx: T, rpc_rx: R) -> Self {
-        let (command_tx, command_rx) = mpsc::channel(100);
         Self {
             rpc_tx,
             rpc_rx,
-            command_tx,
-            command_rx,
             response_subscriptions: Default::default(),
+            next_request_id: Default::default(),
+            request_timeout: Some(DEFAULT_REQUEST_TIMEOUT),
         }
     }
+
+    /// Set the default timeout of the requests sent by the sinks of this runtime, which is
+    /// [`DEFAULT_REQUEST_TIMEOUT`] otherwise. `None` waits for the responses without any timeout.
+    pub fn with_request_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
 }
 
 impl<S, R> RPCPort for RPCRuntime<S, R>
@@ -58,24 +76,14 @@ where
     fn sink(&self) -> impl RPCSink + Clone + Send + 'static {
         RPCSender {
             rpc_tx: self.rpc_tx.clone(),
-            command_tx: self.command_tx.clone(),
+            response_subscriptions: Arc::clone(&self.response_subscriptions),
+            next_request_id: Arc::clone(&self.next_request_id),
+            request_timeout: self.request_timeout,
         }
     }
 
     async fn progress(&mut self) -> anyhow::Result<Option<JSONRPCMessage>> {
-        // 1. Process all pending commands first with priority
-        while let Ok(Some(command)) = self.command_rx.try_next() {
-            match command {
-                Command::WaitResponse {
-                    request_id,
-                    response,
-                } => {
-                    self.response_subscriptions.insert(request_id, response);
-                }
-            }
-        }
-
-        // 2. Try to get a message from the stream, returning None if the stream is closed
+        // Try to get a message from the stream, returning None if the stream is closed
         match self.rpc_rx.next().await {
             Some(Ok(message)) => {
                 match &message {
@@ -103,6 +111,8 @@ where
             }
             Some(Err(e)) => Err(e),
             None => {
+                // No response will arrive anymore, so fail the waiting requests
+                self.response_subscriptions.lock().unwrap().clear();
                 // Return None only when the stream is closed
                 Ok(None)
             }
@@ -115,7 +125,12 @@ where
     S: Sink<JSONRPCMessage> + Unpin + 'static,
 {
     fn handle_response(&mut self, response: &JSONRPCResponse) {
-        if let Some(subscriber) = self.response_subscriptions.remove(&response.id) {
+        let subscriber = self
+            .response_subscriptions
+            .lock()
+            .unwrap()
+            .remove(&response.id);
+        if let Some(subscriber) = subscriber {
             // Ignore errors if the subscriber dropped their receiver
             let _ = subscriber.send(Ok(response.clone()));
             // Return the message anyway so callers can process it if needed
@@ -123,7 +138,12 @@ where
     }
 
     fn handle_error(&mut self, error: &JSONRPCError) {
-        if let Some(subscriber) = self.response_subscriptions.remove(&error.id) {
+        let subscriber = self
+            .response_subscriptions
+            .lock()
+            .unwrap()
+            .remove(&error.id);
+        if let Some(subscriber) = subscriber {
             // Ignore errors if the subscriber dropped their receiver
             let _ = subscriber.send(Err(error.clone()));
             // Return the message anyway so callers can process it if needed
@@ -133,7 +153,7 @@ where
 
 impl<S> RPCSink for RPCSender<S>
 where
-    S: Sink<JSONRPCMessage> + Unpin + Send + Sync,
+    S: Sink<JSONRPCMessage> + Unpin + Clone + Send + Sync + 'static,
 {
     async fn send_message(&mut self, message: JSONRPCMessage) -> anyhow::Result<()> {
         self.rpc_tx
@@ -205,23 +225,16 @@ where
             .await
     }
 
-    async fn request<T: serde::Serialize + Send, R: serde::de::DeserializeOwned + Send>(
+    async fn request_with_options<
+        T: serde::Serialize + Send,
+        R: serde::de::DeserializeOwned + Send,
+    >(
         &mut self,
-        request_id: RequestId,
         method: &str,
         request: T,
+        options: RequestOptions,
     ) -> anyhow::Result<Result<R, JSONRPCError>> {
-        // Create oneshot channel for receiving the response
-        let (response_tx, response_rx) = oneshot::channel();
-
-        // Create command to register response subscriber
-        self.command_tx
-            .send(Command::WaitResponse {
-                request_id: request_id.clone(),
-                response: response_tx,
-            })
-            .await
-            .map_err(|_| anyhow::anyhow!("failed to register response subscriber"))?;
+        let request_id = RequestId::Integer(self.next_request_id.fetch_add(1, Ordering::Relaxed));
 
         // Serialize request to JSON
         let params_value = serde_json::to_value(request)
@@ -239,21 +252,54 @@ where
 
         // Create JSON-RPC request
         let rpc_request = JSONRPCRequest {
-            id: request_id,
+            id: request_id.clone(),
             jsonrpc: Default::default(),
             method: method.to_string(),
             params,
             extra: Default::default(),
         };
 
+        // Register the response subscriber before sending, so that the response cannot arrive
+        // before it
+        let (response_tx, response_rx) = oneshot::channel();
+        self.response_subscriptions
+            .lock()
+            .unwrap()
+            .insert(request_id.clone(), response_tx);
+        let mut pending = PendingRequest {
+            sender: self.clone(),
+            request_id: request_id.clone(),
+            cancel: options.cancel_on_timeout,
+        };
+
         // Send request
-        self.send_message(JSONRPCMessage::JSONRPCRequest(rpc_request))
-            .await?;
+        if let Err(e) = self
+            .send_message(JSONRPCMessage::JSONRPCRequest(rpc_request))
+            .await
+        {
+            // The peer never saw the request, so there is nothing to cancel
+            pending.cancel = false;
+            return Err(e);
+        }
 
         // Wait for response
-        let response = response_rx
-            .await
-            .map_err(|_| anyhow::anyhow!("response channel closed"))?;
+        let timeout = match options.timeout {
+            RequestTimeout::Default => self.request_timeout,
+            RequestTimeout::After(timeout) => Some(timeout),
+            RequestTimeout::Never => None,
+        };
+        let response = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, response_rx).await {
+                Ok(response) => response,
+                Err(_) => {
+                    self.time_out(request_id, timeout, options.cancel_on_timeout)
+                        .await;
+                    return Err(RPCPortError::Timeout(timeout).into());
+                }
+            },
+            None => response_rx.await,
+        }
+        .map_err(|_| anyhow::anyhow!("response channel closed"))?;
 
         // Process result
         match response {
@@ -271,3 +317,231 @@ where
         }
     }
 }
+
+/// A request waiting for its response. If it is dropped before the response arrives, because the
+/// request failed to send, timed out, or its future was dropped, the subscriber is removed and the
+/// peer is told to stop processing it, if requested.
+struct PendingRequest<S>
+where
+    S: Sink<JSONRPCMessage> + Unpin + Clone + Send + Sync + 'static,
+{
+    sender: RPCSender<S>,
+    request_id: RequestId,
+    cancel: bool,
+}
+
+impl<S> Drop for PendingRequest<S>
+where
+    S: Sink<JSONRPCMessage> + Unpin + Clone + Send + Sync + 'static,
+{
+    fn drop(&mut self) {
+        let waiting = self
+            .sender
+            .response_subscriptions
+            .lock()
+            .unwrap()
+            .remove(&self.request_id)
+            .is_some();
+        if !waiting || !self.cancel {
+            return;
+        }
+        // The cancellation is sent in the background, and not at all outside of a tokio runtime
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let mut sender = self.sender.clone();
+        let request_id = self.request_id.clone();
+        runtime.spawn(async move {
+            let _ = sender
+                .send_notification(
+                    "notifications/cancelled",
+                    CancelledNotificationParams {
+                        reason: Some("the request was abandoned".into()),
+                        request_id,
+                        extra: Default::default(),
+                    },
+                )
+                .await;
+        });
+    }
+}
+
+impl<S> RPCSender<S>
+where
+    S: Sink<JSONRPCMessage> + Unpin + Clone + Send + Sync + 'static,
+{
+    /// Forget the timed out request, and tell the peer to stop processing it if requested.
+    async fn time_out(&mut self, request_id: RequestId, timeout: Duration, cancel: bool) {
+        self.response_subscriptions
+            .lock()
+            .unwrap()
+            .remove(&request_id);
+        if cancel {
+            let _ = self
+                .send_notification(
+                    "notifications/cancelled",
+                    CancelledNotificationParams {
+                        reason: Some(format!("timed out after {timeout:?}")),
+                        request_id,
+                        extra: Default::default(),
+                    },
+                )
+                .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::channel::mpsc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let (tx, mut peer_rx) = mpsc::channel(100);
+        let (_peer_tx, rx) = mpsc::channel::<anyhow::Result<JSONRPCMessage>>(100);
+        let runtime = RPCRuntime::new(tx, rx);
+        let mut sink = runtime.sink();
+
+        let error = sink
+            .request_with_options::<_, Value>(
+                "ping",
+                Value::Null,
+                RequestOptions::default().timeout(Duration::from_millis(10)),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RPCPortError>(),
+            Some(RPCPortError::Timeout(_))
+        ));
+
+        let Some(JSONRPCMessage::JSONRPCRequest(request)) = peer_rx.next().await else {
+            panic!("expected the request");
+        };
+        assert_eq!(request.id, RequestId::Integer(0));
+        let Some(JSONRPCMessage::JSONRPCNotification(notification)) = peer_rx.next().await else {
+            panic!("expected the cancellation");
+        };
+        assert_eq!(notification.method, "notifications/cancelled");
+        assert_eq!(notification.params.unwrap().extra["requestId"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_request_without_timeout() {
+        let short = Duration::from_millis(10);
+        for (runtime_timeout, options) in [
+            (Some(short), RequestOptions::default().no_timeout()),
+            (None, RequestOptions::default()),
+        ] {
+            let (tx, mut peer_rx) = mpsc::channel(100);
+            let (mut peer_tx, rx) = mpsc::channel::<anyhow::Result<JSONRPCMessage>>(100);
+            let mut runtime = RPCRuntime::new(tx, rx).with_request_timeout(runtime_timeout);
+            let mut sink = runtime.sink();
+
+            let request = tokio::spawn(async move {
+                sink.request_with_options::<_, Value>("ping", Value::Null, options)
+                    .await
+                    .unwrap()
+                    .unwrap()
+            });
+            let Some(JSONRPCMessage::JSONRPCRequest(request_message)) =
+                within(peer_rx.next()).await
+            else {
+                panic!("expected the request");
+            };
+            // Respond well after the default timeout of the runtime
+            tokio::time::sleep(short * 5).await;
+            peer_tx
+                .send(Ok(JSONRPCMessage::JSONRPCResponse(JSONRPCResponse {
+                    id: request_message.id,
+                    jsonrpc: Default::default(),
+                    result: Default::default(),
+                    extra: Default::default(),
+                })))
+                .await
+                .unwrap();
+            within(runtime.progress()).await.unwrap();
+
+            assert_eq!(within(request).await.unwrap(), serde_json::json!({}));
+            // The request was never cancelled
+            drop(runtime);
+            assert!(peer_rx.next().await.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request() {
+        let (tx, mut peer_rx) = mpsc::channel(100);
+        let (_peer_tx, rx) = mpsc::channel::<anyhow::Result<JSONRPCMessage>>(100);
+        let runtime = RPCRuntime::new(tx, rx);
+        let mut sink = runtime.sink();
+
+        // The request is dropped while waiting for the response
+        let request = sink.request::<_, Value>("ping", Value::Null);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), request)
+                .await
+                .is_err()
+        );
+        assert!(runtime.response_subscriptions.lock().unwrap().is_empty());
+
+        let Some(JSONRPCMessage::JSONRPCRequest(request)) = within(peer_rx.next()).await else {
+            panic!("expected the request");
+        };
+        assert_eq!(request.id, RequestId::Integer(0));
+        let Some(JSONRPCMessage::JSONRPCNotification(notification)) = within(peer_rx.next()).await
+        else {
+            panic!("expected the cancellation");
+        };
+        assert_eq!(notification.method, "notifications/cancelled");
+        assert_eq!(notification.params.unwrap().extra["requestId"], 0);
+    }
+
+    /// Fail instead of hanging if the response is never routed to the request.
+    async fn within<T>(future: impl Future<Output = T>) -> T {
+        tokio::time::timeout(Duration::from_secs(5), future)
+            .await
+            .expect("timed out")
+    }
+
+    #[tokio::test]
+    async fn test_request_while_progress_is_waiting() {
+        let (tx, mut peer_rx) = mpsc::channel(100);
+        let (mut peer_tx, rx) = mpsc::channel::<anyhow::Result<JSONRPCMessage>>(100);
+        let mut runtime = RPCRuntime::new(tx, rx);
+        let mut sink = runtime.sink();
+
+        // The runtime is already waiting for a message when the request is sent
+        let progress = tokio::spawn(async move {
+            let message = runtime.progress().await.unwrap();
+            (runtime, message)
+        });
+        tokio::task::yield_now().await;
+
+        let request = tokio::spawn(async move {
+            sink.request::<_, Value>("ping", Value::Null)
+                .await
+                .unwrap()
+                .unwrap()
+        });
+        let Some(JSONRPCMessage::JSONRPCRequest(request_message)) = within(peer_rx.next()).await
+        else {
+            panic!("expected the request");
+        };
+        peer_tx
+            .send(Ok(JSONRPCMessage::JSONRPCResponse(JSONRPCResponse {
+                id: request_message.id,
+                jsonrpc: Default::default(),
+                result: Default::default(),
+                extra: Default::default(),
+            })))
+            .await
+            .unwrap();
+
+        assert_eq!(within(request).await.unwrap(), serde_json::json!({}));
+        let (_runtime, message) = within(progress).await.unwrap();
+        assert!(matches!(message, Some(JSONRPCMessage::JSONRPCResponse(_))));
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use mmcp_protocol::{
+    mcp::{JSONRPCError, JSONRPCMessage, RequestId},
+    port::{RPCSink, RequestOptions},
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+/// A type-erased [`RPCSink`] to the other side of a connection, so that it can be stored and
+/// shared regardless of the transport.
+#[derive(Clone)]
+pub struct Peer {
+    sink: Arc<dyn ErasedSink + Send + Sync>,
+}
+
+impl Peer {
+    pub fn new<S: RPCSink + Clone + Send + 'static>(sink: S) -> Self {
+        Self {
+            sink: Arc::new(Mutex::new(sink)),
+        }
+    }
+}
+
+trait ErasedSink {
+    fn send_message(&self, message: JSONRPCMessage) -> BoxFuture<'static, anyhow::Result<()>>;
+
+    fn send_notification(
+        &self,
+        method: String,
+        notification: serde_json::Value,
+    ) -> BoxFuture<'static, anyhow::Result<()>>;
+
+    fn send_response(
+        &self,
+        request_id: RequestId,
+        response: serde_json::Value,
+    ) -> BoxFuture<'static, anyhow::Result<()>>;
+
+    fn request(
+        &self,
+        method: String,
+        request: serde_json::Value,
+        options: RequestOptions,
+    ) -> BoxFuture<'static, anyhow::Result<Result<serde_json::Value, JSONRPCError>>>;
+}
+
+impl<S: RPCSink + Clone + Send + 'static> ErasedSink for Mutex<S> {
+    fn send_message(&self, message: JSONRPCMessage) -> BoxFuture<'static, anyhow::Result<()>> {
+        let mut sink = self.lock().unwrap().clone();
+        Box::pin(async move { sink.send_message(message).await })
+    }
+
+    fn send_notification(
+        &self,
+        method: String,
+        notification: serde_json::Value,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        let mut sink = self.lock().unwrap().clone();
+        Box::pin(async move { sink.send_notification(&method, notification).await })
+    }
+
+    fn send_response(
+        &self,
+        request_id: RequestId,
+        response: serde_json::Value,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        let mut sink = self.lock().unwrap().clone();
+        Box::pin(async move { sink.send_response(request_id, response).await })
+    }
+
+    fn request(
+        &self,
+        method: String,
+        request: serde_json::Value,
+        options: RequestOptions,
+    ) -> BoxFuture<'static, anyhow::Result<Result<serde_json::Value, JSONRPCError>>> {
+        let mut sink = self.lock().unwrap().clone();
+        Box::pin(async move {
+            sink.request_with_options::<_, serde_json::Value>(&method, request, options)
+                .await
+        })
+    }
+}
+
+impl RPCSink for Peer {
+    async fn send_message(&mut self, message: JSONRPCMessage) -> anyhow::Result<()> {
+        self.sink.send_message(message).await
+    }
+
+    async fn send_notification<T: Serialize + Send>(
+        &mut self,
+        method: &str,
+        notification: T,
+    ) -> anyhow::Result<()> {
+        let notification = serde_json::to_value(notification)?;
+        self.sink
+            .send_notification(method.to_string(), notification)
+            .await
+    }
+
+    async fn send_response<T: Serialize + Send>(
+        &mut self,
+        request_id: RequestId,
+        response: T,
+    ) -> anyhow::Result<()> {
+        let response = serde_json::to_value(response)?;
+        self.sink.send_response(request_id, response).await
+    }
+
+    async fn request_with_options<T: Serialize + Send, R: DeserializeOwned + Send>(
+        &mut self,
+        method: &str,
+        request: T,
+        options: RequestOptions,
+    ) -> anyhow::Result<Result<R, JSONRPCError>> {
+        let request = serde_json::to_value(request)?;
+        match self
+            .sink
+            .request(method.to_string(), request, options)
+            .await?
+        {
+            Ok(result) => Ok(Ok(serde_json::from_value(result)?)),
+            Err(error) => Ok(Err(error)),
+        }
+    }
+}
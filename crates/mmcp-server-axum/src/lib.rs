@@ -1 +1,59 @@
-// This is a placeholder for the mmcp-server-axum library.
+mod sse;
+mod streamable_http;
+
+pub use sse::LegacySse;
+pub use streamable_http::{SESSION_ID_HEADER, StreamableHttp};
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse as _, Response, sse::Event},
+};
+use futures::{StreamExt as _, channel::mpsc};
+use mmcp_protocol::{consts::error_codes, mcp::JSONRPCMessage};
+use mmcp_rpc::RPCRuntime;
+use mmcp_server::MCPServer;
+
+/// Run a session of the server in the background. Messages from the client are passed through
+/// `incoming`, and the server sends its messages to `outgoing`. `on_close` is called when the
+/// session ends.
+fn spawn_session(
+    server: Arc<MCPServer>,
+    session_id: String,
+    incoming: mpsc::Receiver<JSONRPCMessage>,
+    outgoing: mpsc::Sender<JSONRPCMessage>,
+    on_close: impl FnOnce() + Send + 'static,
+) {
+    tokio::spawn(async move {
+        let port = RPCRuntime::new(outgoing, incoming.map(Ok));
+        if let Err(e) = server.serve(port).await {
+            tracing::warn!("MCP session {session_id} failed: {e}");
+        }
+        on_close();
+    });
+}
+
+/// An SSE event carrying a JSON-RPC message.
+fn message_event(message: &JSONRPCMessage) -> Event {
+    Event::default()
+        .event("message")
+        .data(serde_json::to_string(message).expect("JSON-RPC message is serializable"))
+}
+
+/// The response to a body that is not a JSON-RPC message.
+fn parse_error_response(error: serde_json::Error) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": {
+                "code": error_codes::PARSE_ERROR,
+                "message": format!("Failed to parse JSON-RPC message: {error}"),
+            },
+        })),
+    )
+        .into_response()
+}
@@ -0,0 +1,239 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{get, post},
+};
+use futures::{SinkExt as _, StreamExt as _, channel::mpsc, stream};
+use mmcp_protocol::mcp::JSONRPCMessage;
+use mmcp_server::MCPServer;
+use serde::Deserialize;
+
+use crate::{message_event, parse_error_response, spawn_session};
+
+/// Serves an [`MCPServer`] over the HTTP+SSE transport of the 2024-11-05 specification, for
+/// clients that do not support Streamable HTTP yet.
+///
+/// - `GET` on the SSE endpoint (`/sse` by default) starts a session. The first event is
+///   `endpoint`, telling the client where to post its messages.
+/// - `POST` on the messages endpoint (`/messages?sessionId=...` by default) passes a JSON-RPC
+///   message to the session. All responses are sent on the SSE stream.
+///
+/// The session ends when the client closes the SSE stream.
+#[derive(Clone)]
+pub struct LegacySse {
+    server: Arc<MCPServer>,
+    sessions: Arc<Mutex<HashMap<String, mpsc::Sender<JSONRPCMessage>>>>,
+    sse_path: String,
+    messages_path: String,
+}
+
+impl LegacySse {
+    pub fn new(server: impl Into<Arc<MCPServer>>) -> Self {
+        Self {
+            server: server.into(),
+            sessions: Default::default(),
+            sse_path: "/sse".into(),
+            messages_path: "/messages".into(),
+        }
+    }
+
+    /// Set the path of the SSE endpoint.
+    pub fn sse_path(mut self, path: impl Into<String>) -> Self {
+        self.sse_path = path.into();
+        self
+    }
+
+    /// Set the path of the messages endpoint. It is also sent to the client in the `endpoint`
+    /// event, so it must include the prefix if the router is nested.
+    pub fn messages_path(mut self, path: impl Into<String>) -> Self {
+        self.messages_path = path.into();
+        self
+    }
+
+    /// A router serving the SSE and messages endpoints.
+    pub fn router<S: Clone + Send + Sync + 'static>(self) -> Router<S> {
+        Router::new()
+            .route(&self.sse_path, get(handle_sse))
+            .route(&self.messages_path, post(handle_message))
+            .with_state(self)
+    }
+}
+
+/// Closes the session when the SSE stream is dropped.
+struct SessionGuard {
+    incoming: mpsc::Sender<JSONRPCMessage>,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.incoming.close_channel();
+    }
+}
+
+async fn handle_sse(State(this): State<LegacySse>) -> Response {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let (incoming_tx, incoming_rx) = mpsc::channel(100);
+    let (outgoing_tx, outgoing_rx) = mpsc::channel(100);
+    this.sessions
+        .lock()
+        .unwrap()
+        .insert(session_id.clone(), incoming_tx.clone());
+
+    let sessions = Arc::clone(&this.sessions);
+    let id = session_id.clone();
+    spawn_session(
+        this.server,
+        session_id.clone(),
+        incoming_rx,
+        outgoing_tx,
+        move || {
+            sessions.lock().unwrap().remove(&id);
+        },
+    );
+
+    let endpoint = Event::default()
+        .event("endpoint")
+        .data(format!("{}?sessionId={session_id}", this.messages_path));
+    let guard = SessionGuard {
+        incoming: incoming_tx,
+    };
+    let messages = outgoing_rx.map(move |message| {
+        let _guard = &guard;
+        message_event(&message)
+    });
+    let stream = stream::once(async { endpoint })
+        .chain(messages)
+        .map(Ok::<_, Infallible>);
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[derive(Deserialize)]
+struct MessageQuery {
+    #[serde(rename = "sessionId")]
+    session_id: String,
+}
+
+async fn handle_message(
+    State(this): State<LegacySse>,
+    Query(query): Query<MessageQuery>,
+    body: Bytes,
+) -> Response {
+    let message = match serde_json::from_slice::<JSONRPCMessage>(&body) {
+        Ok(message) => message,
+        Err(e) => return parse_error_response(e),
+    };
+
+    let incoming = this
+        .sessions
+        .lock()
+        .unwrap()
+        .get(&query.session_id)
+        .cloned();
+    let Some(mut incoming) = incoming else {
+        return (StatusCode::NOT_FOUND, "Session not found").into_response();
+    };
+    match incoming.send(message).await {
+        Ok(()) => StatusCode::ACCEPTED.into_response(),
+        Err(_) => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, header},
+    };
+    use http_body_util::BodyExt as _;
+    use serde_json::{Value, json};
+    use tower::ServiceExt as _;
+
+    use super::*;
+    use crate::StreamableHttp;
+
+    /// Read the body until an event with `data` arrives, and return the data.
+    async fn next_data(body: &mut Body) -> String {
+        let mut buffer = String::new();
+        loop {
+            let frame = body.frame().await.unwrap().unwrap();
+            buffer.push_str(std::str::from_utf8(&frame.into_data().unwrap()).unwrap());
+            if let Some(data) = buffer.lines().find_map(|line| line.strip_prefix("data: ")) {
+                return data.to_string();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_legacy_sse() {
+        // Both transports share the server and can be mounted on one app
+        let server = Arc::new(MCPServer::new("test", "0.1.0"));
+        let app: Router = StreamableHttp::new(Arc::clone(&server))
+            .router("/mcp")
+            .merge(LegacySse::new(server).router());
+
+        let response = app
+            .clone()
+            .oneshot(Request::get("/sse").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = response.into_body();
+        let endpoint = next_data(&mut body).await;
+        assert!(endpoint.starts_with("/messages?sessionId="));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post(&endpoint)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(
+                        json!({
+                            "jsonrpc": "2.0",
+                            "id": 1,
+                            "method": "initialize",
+                            "params": {
+                                "capabilities": {},
+                                "clientInfo": {"name": "test-client", "version": "0.1.0"},
+                                "protocolVersion": "2024-11-05"
+                            }
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let response: Value = serde_json::from_str(&next_data(&mut body).await).unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+
+        let response = app
+            .oneshot(
+                Request::post("/messages?sessionId=unknown")
+                    .body(Body::from(
+                        json!({"jsonrpc": "2.0", "method": "notifications/initialized"})
+                            .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
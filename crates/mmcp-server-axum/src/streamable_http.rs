@@ -0,0 +1,696 @@
+mod session;
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{
+        IntoResponse, Response,
+        sse::{KeepAlive, Sse},
+    },
+    routing::MethodRouter,
+};
+use futures::{StreamExt as _, channel::mpsc};
+use mmcp_protocol::{
+    consts::error_codes,
+    mcp::{JSONRPCMessage, JsonrpcBatchRequestItem, RequestId},
+};
+use mmcp_server::MCPServer;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+use self::session::HttpSession;
+use crate::{message_event, parse_error_response, spawn_session};
+
+/// The header carrying the session id assigned on initialization.
+pub const SESSION_ID_HEADER: &str = "mcp-session-id";
+
+/// How long a session is kept while the client sends nothing and listens on no stream, unless
+/// [`StreamableHttp::session_timeout`] says otherwise.
+pub const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Serves an [`MCPServer`] over the Streamable HTTP transport of the 2025-03-26 specification.
+///
+/// - `POST` accepts a JSON-RPC message or batch. Requests are answered with an SSE stream carrying
+///   the responses and the messages sent by the server meanwhile, or with a JSON body if
+///   [`json_response`](Self::json_response) is enabled or the client does not accept SSE.
+/// - `GET` opens an SSE stream for messages the server sends outside of any request.
+/// - `DELETE` terminates the session.
+///
+/// An `initialize` request starts a new session, whose id is returned in the `Mcp-Session-Id`
+/// header and must be sent with every following request. The session ends when it is deleted,
+/// when its `initialize` is answered with an error, or when it stays idle for the
+/// [`session_timeout`](Self::session_timeout).
+#[derive(Clone)]
+pub struct StreamableHttp {
+    server: Arc<MCPServer>,
+    sessions: Arc<Mutex<HashMap<String, Arc<HttpSession>>>>,
+    json_response: bool,
+    session_timeout: Duration,
+}
+
+impl StreamableHttp {
+    pub fn new(server: impl Into<Arc<MCPServer>>) -> Self {
+        Self {
+            server: server.into(),
+            sessions: Default::default(),
+            json_response: false,
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+        }
+    }
+
+    /// Answer requests with a plain JSON body instead of an SSE stream. Messages the server sends
+    /// while handling a request are then only delivered on the stream opened with `GET`, so
+    /// sampling and roots requests from tools need the client to keep that stream open, or they
+    /// are dropped and wait until they time out.
+    pub fn json_response(mut self, json_response: bool) -> Self {
+        self.json_response = json_response;
+        self
+    }
+
+    /// End sessions whose client sends nothing and listens on no stream for the timeout,
+    /// [`DEFAULT_SESSION_TIMEOUT`] by default.
+    pub fn session_timeout(mut self, session_timeout: Duration) -> Self {
+        self.session_timeout = session_timeout;
+        self
+    }
+
+    /// The handlers of the MCP endpoint, to be mounted with [`Router::route`].
+    pub fn method_router<S: Clone + Send + Sync + 'static>(self) -> MethodRouter<S> {
+        MethodRouter::new()
+            .post(handle_post)
+            .get(handle_get)
+            .delete(handle_delete)
+            .with_state(self)
+    }
+
+    /// A router serving the MCP endpoint at the path.
+    pub fn router<S: Clone + Send + Sync + 'static>(self, path: &str) -> Router<S> {
+        Router::new().route(path, self.method_router())
+    }
+
+    /// Start a session for the `initialize` request with the id, which is ended if the request is
+    /// answered with an error.
+    fn create_session(&self, initialize_id: RequestId) -> (String, Arc<HttpSession>) {
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let (incoming_tx, incoming_rx) = mpsc::channel(100);
+        let (outgoing_tx, mut outgoing_rx) = mpsc::channel(100);
+        let session = Arc::new(HttpSession::new(incoming_tx));
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session_id.clone(), Arc::clone(&session));
+
+        let routes = Arc::clone(&session);
+        let sessions = Arc::clone(&self.sessions);
+        let id = session_id.clone();
+        tokio::spawn(async move {
+            while let Some(message) = outgoing_rx.next().await {
+                let rejected = matches!(
+                    &message,
+                    JSONRPCMessage::JSONRPCError(error) if error.id == initialize_id
+                );
+                if rejected {
+                    // Removed before the error is routed, so the response does not carry its id
+                    sessions.lock().unwrap().remove(&id);
+                }
+                routes.route(message);
+                if rejected {
+                    routes.close();
+                }
+            }
+        });
+        self.expire_when_idle(session_id.clone(), Arc::clone(&session));
+
+        let sessions = Arc::clone(&self.sessions);
+        let id = session_id.clone();
+        spawn_session(
+            Arc::clone(&self.server),
+            session_id.clone(),
+            incoming_rx,
+            outgoing_tx,
+            move || {
+                sessions.lock().unwrap().remove(&id);
+            },
+        );
+
+        (session_id, session)
+    }
+
+    /// End the session once it stays idle for the session timeout.
+    fn expire_when_idle(&self, session_id: String, session: Arc<HttpSession>) {
+        let sessions = Arc::clone(&self.sessions);
+        let timeout = self.session_timeout;
+        tokio::spawn(async move {
+            let mut wait = timeout;
+            loop {
+                tokio::time::sleep(wait).await;
+                let ended = !sessions
+                    .lock()
+                    .unwrap()
+                    .get(&session_id)
+                    .is_some_and(|current| Arc::ptr_eq(current, &session));
+                if ended {
+                    return;
+                }
+                wait = match session.idle_for() {
+                    Some(idle) if idle >= timeout => {
+                        sessions.lock().unwrap().remove(&session_id);
+                        session.close();
+                        return;
+                    }
+                    Some(idle) => timeout - idle,
+                    None => timeout,
+                };
+            }
+        });
+    }
+
+    /// Find the session of the request from its `Mcp-Session-Id` header.
+    fn session(&self, headers: &HeaderMap) -> Result<Arc<HttpSession>, SessionError> {
+        let session_id = session_id(headers).ok_or(SessionError::Missing)?;
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .cloned()
+            .ok_or(SessionError::NotFound)
+    }
+}
+
+enum SessionError {
+    Missing,
+    NotFound,
+}
+
+impl IntoResponse for SessionError {
+    fn into_response(self) -> Response {
+        match self {
+            SessionError::Missing => {
+                (StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id header").into_response()
+            }
+            SessionError::NotFound => (StatusCode::NOT_FOUND, "Session not found").into_response(),
+        }
+    }
+}
+
+async fn handle_post(
+    State(this): State<StreamableHttp>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let message = match serde_json::from_slice::<JSONRPCMessage>(&body) {
+        Ok(message) => message,
+        Err(e) => return parse_error_response(e),
+    };
+
+    let (new_session_id, session) = if let Some(initialize_id) = initialize_id(&message) {
+        let (session_id, session) = this.create_session(initialize_id);
+        (Some(session_id), session)
+    } else {
+        match this.session(&headers) {
+            Ok(session) => (None, session),
+            Err(e) => return e.into_response(),
+        }
+    };
+
+    let request_ids = request_ids(&message);
+    if request_ids.is_empty() {
+        // Notifications and responses are only acknowledged
+        return match session.send(message).await {
+            Ok(()) => StatusCode::ACCEPTED.into_response(),
+            Err(_) => SessionError::NotFound.into_response(),
+        };
+    }
+
+    let sse = !this.json_response && accepts(&headers, "text/event-stream");
+    let rx = match session.open_stream(request_ids.clone(), sse) {
+        Ok(rx) => rx,
+        Err(request_id) => return duplicate_request_response(request_id),
+    };
+    if session.send(message).await.is_err() {
+        // The server never saw the requests, so their ids must not stay in flight
+        session.release(&request_ids);
+        return SessionError::NotFound.into_response();
+    }
+
+    let mut response = if sse {
+        sse_response(rx)
+    } else {
+        json_response(rx).await
+    };
+    // A JSON response has already been awaited, so a rejected initialize has removed its session
+    let created = new_session_id
+        .filter(|session_id| sse || this.sessions.lock().unwrap().contains_key(session_id));
+    if let Some(session_id) = created {
+        response.headers_mut().insert(
+            SESSION_ID_HEADER,
+            HeaderValue::from_str(&session_id).expect("UUID should be a valid header value"),
+        );
+    }
+    response
+}
+
+async fn handle_get(State(this): State<StreamableHttp>, headers: HeaderMap) -> Response {
+    if !accepts(&headers, "text/event-stream") {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+    match this.session(&headers) {
+        Ok(session) => sse_response(session.open_standalone_stream()),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn handle_delete(State(this): State<StreamableHttp>, headers: HeaderMap) -> Response {
+    let Some(session_id) = session_id(&headers) else {
+        return SessionError::Missing.into_response();
+    };
+    let Some(session) = this.sessions.lock().unwrap().remove(session_id) else {
+        return SessionError::NotFound.into_response();
+    };
+    session.close();
+    StatusCode::OK.into_response()
+}
+
+fn sse_response(rx: UnboundedReceiver<JSONRPCMessage>) -> Response {
+    let stream = UnboundedReceiverStream::new(rx)
+        .map(|message| Ok::<_, Infallible>(message_event(&message)));
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Wait for the responses and return them as a JSON body, discarding any other message.
+async fn json_response(mut rx: UnboundedReceiver<JSONRPCMessage>) -> Response {
+    while let Some(message) = rx.recv().await {
+        if matches!(
+            message,
+            JSONRPCMessage::JSONRPCResponse(_)
+                | JSONRPCMessage::JSONRPCError(_)
+                | JSONRPCMessage::JSONRPCBatchResponse(_)
+        ) {
+            return Json(message).into_response();
+        }
+    }
+    // The request was cancelled, so there is no response
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// The response to a request whose id is already in flight in the session.
+fn duplicate_request_response(request_id: RequestId) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request_id,
+            "error": {
+                "code": error_codes::INVALID_REQUEST,
+                "message": "The request id is already in flight in the session",
+            },
+        })),
+    )
+        .into_response()
+}
+
+fn session_id(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(SESSION_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// The id of the message if it is an `initialize` request.
+fn initialize_id(message: &JSONRPCMessage) -> Option<RequestId> {
+    match message {
+        JSONRPCMessage::JSONRPCRequest(request) if request.method == "initialize" => {
+            Some(request.id.clone())
+        }
+        _ => None,
+    }
+}
+
+/// The ids of the requests in the message, which must be responded.
+fn request_ids(message: &JSONRPCMessage) -> Vec<RequestId> {
+    match message {
+        JSONRPCMessage::JSONRPCRequest(request) => vec![request.id.clone()],
+        JSONRPCMessage::JSONRPCBatchRequest(batch) => batch
+            .0
+            .iter()
+            .filter_map(|item| match item {
+                JsonrpcBatchRequestItem::JSONRPCRequest(request) => Some(request.id.clone()),
+                JsonrpcBatchRequestItem::JSONRPCNotification(_) => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn accepts(headers: &HeaderMap, mime: &str) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|media_range| media_range.split(';').next().unwrap_or_default().trim())
+        .any(|media_range| media_range == mime || media_range == "*/*")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, pin::Pin, time::Duration};
+
+    use axum::{body::Body, http::Request};
+    use http_body_util::BodyExt as _;
+    use mmcp_protocol::mcp::{CallToolRequest, CallToolResult, ToolAnnotations};
+    use mmcp_server::primitives::tool::{IntoToolResult as _, Tool, ToolContext};
+    use serde_json::{Value, json};
+    use tower::ServiceExt as _;
+
+    use super::*;
+
+    /// A tool that only finishes when cancelled.
+    struct Wait;
+
+    impl Tool for Wait {
+        fn name(&self) -> Cow<'static, str> {
+            "wait".into()
+        }
+
+        fn description(&self) -> Cow<'static, str> {
+            "Wait forever".into()
+        }
+
+        fn input_schema(&self) -> Cow<'static, str> {
+            r#"{"type": "object"}"#.into()
+        }
+
+        fn annotations(&self) -> ToolAnnotations {
+            Default::default()
+        }
+
+        fn execute(
+            &self,
+            _request: CallToolRequest,
+            _context: ToolContext,
+        ) -> Pin<Box<dyn Future<Output = CallToolResult> + Send + '_>> {
+            Box::pin(async {
+                futures::future::pending::<()>().await;
+                "done".into_tool_result()
+            })
+        }
+    }
+
+    fn app(json_response: bool) -> Router {
+        StreamableHttp::new(MCPServer::new("test", "0.1.0").add_tool(Wait))
+            .json_response(json_response)
+            .router("/mcp")
+    }
+
+    fn post(session_id: Option<&str>, body: Value) -> Request<Body> {
+        let mut request = Request::post("/mcp")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "application/json, text/event-stream");
+        if let Some(session_id) = session_id {
+            request = request.header(SESSION_ID_HEADER, session_id);
+        }
+        request.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn body(response: Response) -> String {
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    /// Initialize a session and return its id.
+    async fn initialize(app: &Router) -> String {
+        let response = app
+            .clone()
+            .oneshot(post(
+                None,
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "initialize",
+                    "params": {
+                        "capabilities": {},
+                        "clientInfo": {"name": "test-client", "version": "0.1.0"},
+                        "protocolVersion": "2025-03-26"
+                    }
+                }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let session_id = response.headers()[SESSION_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let response = app
+            .clone()
+            .oneshot(post(
+                Some(&session_id),
+                json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        session_id
+    }
+
+    #[tokio::test]
+    async fn test_json_response() {
+        let app = app(true);
+        let session_id = initialize(&app).await;
+
+        let response = app
+            .clone()
+            .oneshot(post(
+                Some(&session_id),
+                json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            serde_json::from_str::<Value>(&body(response).await).unwrap(),
+            json!({"jsonrpc": "2.0", "id": 2, "result": {"tools": [{
+                "name": "wait",
+                "description": "Wait forever",
+                "inputSchema": {"type": "object"},
+                "annotations": {}
+            }]}})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request() {
+        let app = app(true);
+        let session_id = initialize(&app).await;
+        let call = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {
+            "name": "wait", "arguments": {}
+        }});
+
+        let mut waiting = app.clone().oneshot(post(Some(&session_id), call.clone()));
+        assert!(futures::poll!(&mut waiting).is_pending());
+
+        // The id is in flight, so it cannot be reused yet
+        let response = app
+            .clone()
+            .oneshot(post(Some(&session_id), call))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error = serde_json::from_str::<Value>(&body(response).await).unwrap();
+        assert_eq!(error["id"], 2);
+        assert_eq!(error["error"]["code"], error_codes::INVALID_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(post(
+                Some(&session_id),
+                json!({"jsonrpc": "2.0", "method": "notifications/cancelled", "params": {
+                    "requestId": 2
+                }}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        // The cancelled request gets no response, so its POST ends without a body
+        let response = tokio::time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .expect("the cancelled request should not hang")
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_request_ids_in_batch() {
+        let app = app(true);
+        let session_id = initialize(&app).await;
+
+        let response = app
+            .clone()
+            .oneshot(post(
+                Some(&session_id),
+                json!([
+                    {"jsonrpc": "2.0", "id": 2, "method": "ping"},
+                    {"jsonrpc": "2.0", "id": 2, "method": "ping"}
+                ]),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_request_ids_released_when_send_fails() {
+        let (incoming, closed) = mpsc::channel(1);
+        drop(closed);
+        let session = HttpSession::new(incoming);
+        let request_ids = vec![RequestId::Integer(2)];
+
+        let _rx = session.open_stream(request_ids.clone(), false).unwrap();
+        let ping = serde_json::from_value(json!({"jsonrpc": "2.0", "id": 2, "method": "ping"}));
+        assert!(session.send(ping.unwrap()).await.is_err());
+        session.release(&request_ids);
+
+        assert!(session.open_stream(request_ids, false).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_initialize() {
+        let app = app(true);
+
+        let response = app
+            .clone()
+            .oneshot(post(
+                None,
+                json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key(SESSION_ID_HEADER));
+        let error = serde_json::from_str::<Value>(&body(response).await).unwrap();
+        assert_eq!(error["error"]["code"], error_codes::INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_idle_session() {
+        let timeout = Duration::from_millis(50);
+        let app = StreamableHttp::new(MCPServer::new("test", "0.1.0"))
+            .json_response(true)
+            .session_timeout(timeout)
+            .router("/mcp");
+        let ping = json!({"jsonrpc": "2.0", "id": 2, "method": "ping"});
+
+        // A client listening on the GET stream is not idle
+        let session_id = initialize(&app).await;
+        let stream = app
+            .clone()
+            .oneshot(
+                Request::get("/mcp")
+                    .header(header::ACCEPT, "text/event-stream")
+                    .header(SESSION_ID_HEADER, &session_id)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        tokio::time::sleep(timeout * 4).await;
+        let response = app
+            .clone()
+            .oneshot(post(Some(&session_id), ping.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        drop(stream);
+        tokio::time::sleep(timeout * 4).await;
+        let response = app
+            .clone()
+            .oneshot(post(Some(&session_id), ping))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_sse_response() {
+        let app = app(false);
+        let session_id = initialize(&app).await;
+
+        let response = app
+            .clone()
+            .oneshot(post(
+                Some(&session_id),
+                json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        let body = body(response).await;
+        let data = body
+            .lines()
+            .find_map(|line| line.strip_prefix("data: "))
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(data).unwrap()["result"]["tools"][0]["name"],
+            "wait"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_header() {
+        let app = app(true);
+        let request = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"});
+
+        let response = app
+            .clone()
+            .oneshot(post(None, request.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .clone()
+            .oneshot(post(Some("unknown"), request.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let session_id = initialize(&app).await;
+        let response = app
+            .clone()
+            .oneshot(
+                Request::delete("/mcp")
+                    .header(SESSION_ID_HEADER, &session_id)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(post(Some(&session_id), request))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
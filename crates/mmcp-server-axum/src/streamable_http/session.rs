@@ -0,0 +1,254 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use futures::{SinkExt as _, channel::mpsc};
+use mmcp_protocol::mcp::{
+    JSONRPCMessage, JSONRPCNotification, JsonrpcBatchRequestItem, JsonrpcBatchResponseItem,
+    RequestId,
+};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+/// An MCP session over HTTP. Routes the messages sent by the server to the HTTP responses open for
+/// the session.
+pub(crate) struct HttpSession {
+    incoming: mpsc::Sender<JSONRPCMessage>,
+    routes: Mutex<Routes>,
+    /// When the client last sent a message or opened a stream
+    last_active: Mutex<Instant>,
+}
+
+#[derive(Default)]
+struct Routes {
+    next_stream_id: u64,
+    streams: HashMap<u64, Stream>,
+    /// The stream waiting for the response of each request
+    requests: HashMap<RequestId, u64>,
+    /// The stream opened with GET, for messages unrelated to any request
+    standalone: Option<UnboundedSender<JSONRPCMessage>>,
+}
+
+struct Stream {
+    tx: UnboundedSender<JSONRPCMessage>,
+    /// Whether the stream can carry messages other than the responses, i.e. it is an SSE stream
+    notifications: bool,
+}
+
+impl HttpSession {
+    pub(crate) fn new(incoming: mpsc::Sender<JSONRPCMessage>) -> Self {
+        Self {
+            incoming,
+            routes: Default::default(),
+            last_active: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Pass a message from the client to the server. The streams of the requests it cancels are
+    /// released, since the server will not respond to them.
+    pub(crate) async fn send(&self, message: JSONRPCMessage) -> anyhow::Result<()> {
+        self.touch();
+        self.release(&cancelled_request_ids(&message));
+        self.incoming
+            .clone()
+            .send(message)
+            .await
+            .map_err(|_| anyhow::anyhow!("session closed"))
+    }
+
+    /// Stop passing messages to the server, which ends the session.
+    pub(crate) fn close(&self) {
+        self.incoming.clone().close_channel();
+    }
+
+    /// Open a stream receiving the responses to the requests. It is closed once all of them are
+    /// responded or cancelled.
+    ///
+    /// Fails with the first id already in flight in the session, or repeated in `request_ids`,
+    /// since its response could not be told apart.
+    pub(crate) fn open_stream(
+        &self,
+        request_ids: Vec<RequestId>,
+        notifications: bool,
+    ) -> Result<UnboundedReceiver<JSONRPCMessage>, RequestId> {
+        self.touch();
+        let mut routes = self.routes.lock().unwrap();
+        for (i, request_id) in request_ids.iter().enumerate() {
+            if routes.requests.contains_key(request_id) || request_ids[..i].contains(request_id) {
+                return Err(request_id.clone());
+            }
+        }
+        let (tx, rx) = unbounded_channel();
+        let stream_id = routes.next_stream_id;
+        routes.next_stream_id += 1;
+        routes
+            .streams
+            .insert(stream_id, Stream { tx, notifications });
+        for request_id in request_ids {
+            routes.requests.insert(request_id, stream_id);
+        }
+        Ok(rx)
+    }
+
+    /// Stop waiting for the responses of requests that could not be passed to the server.
+    pub(crate) fn release(&self, request_ids: &[RequestId]) {
+        let mut routes = self.routes.lock().unwrap();
+        for request_id in request_ids {
+            routes.release(request_id);
+        }
+    }
+
+    /// Open the stream for messages unrelated to any request, replacing the previous one.
+    pub(crate) fn open_standalone_stream(&self) -> UnboundedReceiver<JSONRPCMessage> {
+        self.touch();
+        let (tx, rx) = unbounded_channel();
+        self.routes.lock().unwrap().standalone = Some(tx);
+        rx
+    }
+
+    /// How long the client has been idle, or `None` while it still listens on a stream.
+    pub(crate) fn idle_for(&self) -> Option<Duration> {
+        let routes = self.routes.lock().unwrap();
+        let listening = routes.streams.values().any(|stream| !stream.tx.is_closed())
+            || routes
+                .standalone
+                .as_ref()
+                .is_some_and(|standalone| !standalone.is_closed());
+        if listening {
+            return None;
+        }
+        Some(self.last_active.lock().unwrap().elapsed())
+    }
+
+    fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    /// Route a message from the server to the client.
+    pub(crate) fn route(&self, message: JSONRPCMessage) {
+        let mut routes = self.routes.lock().unwrap();
+        match &message {
+            JSONRPCMessage::JSONRPCResponse(response) => {
+                let request_id = response.id.clone();
+                routes.respond(&request_id, message);
+            }
+            JSONRPCMessage::JSONRPCError(error) => {
+                let request_id = error.id.clone();
+                routes.respond(&request_id, message);
+            }
+            JSONRPCMessage::JSONRPCBatchResponse(batch) => {
+                let request_id = batch.0.first().map(|item| match item {
+                    JsonrpcBatchResponseItem::JSONRPCResponse(response) => response.id.clone(),
+                    JsonrpcBatchResponseItem::JSONRPCError(error) => error.id.clone(),
+                });
+                if let Some(request_id) = request_id {
+                    routes.respond_batch(&request_id, message);
+                }
+            }
+            _ => routes.notify(message),
+        }
+    }
+}
+
+impl Routes {
+    fn respond(&mut self, request_id: &RequestId, message: JSONRPCMessage) {
+        let Some(stream_id) = self.requests.remove(request_id) else {
+            return;
+        };
+        if let Some(stream) = self.streams.get(&stream_id) {
+            let _ = stream.tx.send(message);
+        }
+        self.close_if_done(stream_id);
+    }
+
+    /// Stop waiting for the response of a cancelled request.
+    fn release(&mut self, request_id: &RequestId) {
+        if let Some(stream_id) = self.requests.remove(request_id) {
+            self.close_if_done(stream_id);
+        }
+    }
+
+    /// Close the stream once no request is waiting on it.
+    fn close_if_done(&mut self, stream_id: u64) {
+        if !self.requests.values().any(|id| *id == stream_id) {
+            self.streams.remove(&stream_id);
+        }
+    }
+
+    /// A batch is responded at once, so the stream is closed even if some requests got no response.
+    fn respond_batch(&mut self, request_id: &RequestId, message: JSONRPCMessage) {
+        let Some(&stream_id) = self.requests.get(request_id) else {
+            return;
+        };
+        if let Some(stream) = self.streams.remove(&stream_id) {
+            let _ = stream.tx.send(message);
+        }
+        self.requests.retain(|_, id| *id != stream_id);
+    }
+
+    /// Send a notification or request to the standalone stream, or to the latest SSE stream if the
+    /// client has not opened one. Dropped with a warning if there is no stream to send it to.
+    fn notify(&mut self, message: JSONRPCMessage) {
+        if let Some(standalone) = &self.standalone {
+            match standalone.send(message) {
+                Ok(()) => return,
+                Err(error) => {
+                    self.standalone = None;
+                    return self.notify(error.0);
+                }
+            }
+        }
+        let latest = self
+            .streams
+            .iter()
+            .filter(|(_, stream)| stream.notifications)
+            .max_by_key(|(id, _)| **id);
+        match latest {
+            Some((_, stream)) => {
+                let _ = stream.tx.send(message);
+            }
+            None => tracing::warn!(
+                "Dropped a message to the client, which listens on no SSE stream: {}",
+                message_method(&message).unwrap_or("unknown")
+            ),
+        }
+    }
+}
+
+/// The ids of the requests cancelled by the `notifications/cancelled` in the message.
+fn cancelled_request_ids(message: &JSONRPCMessage) -> Vec<RequestId> {
+    match message {
+        JSONRPCMessage::JSONRPCNotification(notification) => {
+            cancelled_request_id(notification).into_iter().collect()
+        }
+        JSONRPCMessage::JSONRPCBatchRequest(batch) => batch
+            .0
+            .iter()
+            .filter_map(|item| match item {
+                JsonrpcBatchRequestItem::JSONRPCNotification(notification) => {
+                    cancelled_request_id(notification)
+                }
+                JsonrpcBatchRequestItem::JSONRPCRequest(_) => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn cancelled_request_id(notification: &JSONRPCNotification) -> Option<RequestId> {
+    if notification.method != "notifications/cancelled" {
+        return None;
+    }
+    let request_id = notification.params.as_ref()?.extra.get("requestId")?;
+    serde_json::from_value(request_id.clone()).ok()
+}
+
+/// The method of a request or notification.
+fn message_method(message: &JSONRPCMessage) -> Option<&str> {
+    match message {
+        JSONRPCMessage::JSONRPCRequest(request) => Some(&request.method),
+        JSONRPCMessage::JSONRPCNotification(notification) => Some(&notification.method),
+        _ => None,
+    }
+}
@@ -1,38 +1,108 @@
-use futures::{StreamExt, TryStreamExt};
-use mmcp_protocol::{mcp::JSONRPCMessage, port::RPCPort};
+use futures::{StreamExt, TryStreamExt, channel::mpsc, stream};
+use mmcp_protocol::{
+    mcp::{JSONRPCMessage, JsonrpcErrorError},
+    port::{RPCPort, RPCSink},
+};
 use mmcp_rpc::RPCRuntime;
+use mmcp_server::error::McpError;
 use std::io::Write;
-use tokio::io::AsyncBufReadExt;
+use tokio::{io::AsyncBufReadExt, task::JoinHandle};
 use tokio_stream::wrappers::LinesStream;
 
 pub fn stdio_server_rpc() -> impl RPCPort {
-    let (tx, mut rx) = futures::channel::mpsc::channel::<JSONRPCMessage>(100);
+    let (tx, rx) = mpsc::channel::<JSONRPCMessage>(100);
+    // Malformed lines are answered directly, since they are never passed to the server
+    let (parse_errors, parse_errors_rx) = mpsc::unbounded::<String>();
+    let errors = parse_errors.clone();
     let stream = LinesStream::new(tokio::io::BufReader::new(tokio::io::stdin()).lines())
         .map_err(anyhow::Error::from)
-        .map_ok(|line| {
-            Box::pin(async move {
-                match serde_json::from_str::<JSONRPCMessage>(&line) {
-                    Ok(message) => Some(message),
-                    Err(e) => {
-                        eprintln!("Error parsing JSON: {}: {}", e, line);
-                        None
-                    }
-                }
-            })
-        })
-        .try_filter_map(|message| Box::pin(async move { Ok(message.await) }));
+        .try_filter_map(move |line| {
+            let message = parse_line(&line)
+                .map_err(|response| {
+                    let _ = errors.unbounded_send(response);
+                })
+                .ok();
+            Box::pin(async move { Ok(message) })
+        });
     let mut writer = std::io::BufWriter::new(std::io::stdout());
 
-    let rpc = RPCRuntime::new(tx, stream);
+    let rpc = RPCRuntime::new(tx.clone(), stream);
 
     // forward the stream to the channel and enforce tx close on stream close.
-    tokio::spawn(async move {
-        while let Some(message) = rx.next().await {
-            let json = serde_json::to_string(&message).unwrap();
+    let mut lines = stream::select(
+        rx.map(|message| serde_json::to_string(&message).unwrap()),
+        parse_errors_rx,
+    );
+    let writer = tokio::spawn(async move {
+        while let Some(json) = lines.next().await {
             writeln!(&mut writer, "{}", json).unwrap();
             writer.flush().unwrap();
         }
     });
 
-    rpc
+    StdioServerRpc {
+        rpc,
+        tx,
+        parse_errors,
+        writer: Some(writer),
+    }
+}
+
+/// Parse a line of stdin, or return the `PARSE_ERROR` response to write back if it is not a
+/// JSON-RPC message. The response has a null id, since the id of the request is unknown.
+fn parse_line(line: &str) -> Result<JSONRPCMessage, String> {
+    serde_json::from_str(line).map_err(|e| {
+        let error = McpError::parse_error(format!("Failed to parse JSON-RPC message: {e}"));
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": JsonrpcErrorError::from(error),
+        })
+        .to_string()
+    })
+}
+
+struct StdioServerRpc<P> {
+    rpc: P,
+    tx: mpsc::Sender<JSONRPCMessage>,
+    parse_errors: mpsc::UnboundedSender<String>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl<P: RPCPort + Send> RPCPort for StdioServerRpc<P> {
+    fn sink(&self) -> impl RPCSink + Clone + Send + 'static {
+        self.rpc.sink()
+    }
+
+    async fn progress(&mut self) -> anyhow::Result<Option<JSONRPCMessage>> {
+        self.rpc.progress().await
+    }
+
+    /// Close the channel and wait for the messages in it to be written to stdout.
+    async fn close(&mut self) -> anyhow::Result<()> {
+        self.tx.close_channel();
+        self.parse_errors.close_channel();
+        if let Some(writer) = self.writer.take() {
+            writer.await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mmcp_protocol::consts::error_codes;
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        assert!(parse_line(r#"{"jsonrpc": "2.0", "id": 1, "method": "ping"}"#).is_ok());
+
+        let response = serde_json::from_str::<Value>(&parse_line("{not json").unwrap_err());
+        let response = response.unwrap();
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], json!(error_codes::PARSE_ERROR));
+    }
 }
@@ -0,0 +1,125 @@
+use mmcp_protocol::{
+    consts::error_codes,
+    mcp::{JSONRPCError, JsonrpcErrorError, RequestId},
+};
+use serde_json::Value;
+
+/// An error answered to the client as a JSON-RPC error response.
+///
+/// Primitives returning `anyhow::Error` can wrap an `McpError` to choose the code sent to the
+/// client; any other error is answered with [`INTERNAL_ERROR`](error_codes::INTERNAL_ERROR).
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{message}")]
+pub struct McpError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl McpError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn parse_error(message: impl Into<String>) -> Self {
+        Self::new(error_codes::PARSE_ERROR, message)
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(error_codes::INVALID_REQUEST, message)
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(
+            error_codes::METHOD_NOT_FOUND,
+            format!("Method not found: {method}"),
+        )
+        .with_data(serde_json::json!({"method": method}))
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(error_codes::INVALID_PARAMS, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(error_codes::INTERNAL_ERROR, message)
+    }
+
+    pub fn resource_not_found(uri: &str) -> Self {
+        Self::new(
+            error_codes::RESOURCE_NOT_FOUND,
+            format!("Resource not found: {uri}"),
+        )
+        .with_data(serde_json::json!({"uri": uri}))
+    }
+
+    /// The error response to the request with the id.
+    pub fn into_response(self, id: RequestId) -> JSONRPCError {
+        JSONRPCError {
+            error: self.into(),
+            id,
+            jsonrpc: Default::default(),
+            extra: Default::default(),
+        }
+    }
+}
+
+impl From<McpError> for JsonrpcErrorError {
+    fn from(error: McpError) -> Self {
+        JsonrpcErrorError {
+            code: error.code,
+            message: error.message,
+            data: error.data,
+            extra: Default::default(),
+        }
+    }
+}
+
+impl From<JsonrpcErrorError> for McpError {
+    fn from(error: JsonrpcErrorError) -> Self {
+        Self {
+            code: error.code,
+            message: error.message,
+            data: error.data,
+        }
+    }
+}
+
+impl From<anyhow::Error> for McpError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<McpError>() {
+            Ok(error) => error,
+            Err(error) => Self::internal(format!("{error:#}")),
+        }
+    }
+}
+
+impl From<serde_json::Error> for McpError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::internal(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_anyhow() {
+        let error = McpError::from(anyhow::Error::from(McpError::invalid_params("bad")));
+        assert_eq!(error, McpError::invalid_params("bad"));
+
+        let error = McpError::from(anyhow::anyhow!("boom").context("reading"));
+        assert_eq!(error.code, error_codes::INTERNAL_ERROR);
+        assert_eq!(error.message, "reading: boom");
+    }
+}
@@ -1,6 +1,10 @@
 pub use ::inventory::*;
 
-use crate::primitives::tool::{BoxedTool, Tool};
+use crate::primitives::{
+    prompt::{BoxedPrompt, Prompt},
+    resource::{BoxedResource, BoxedResourceTemplate, Resource, ResourceTemplate},
+    tool::{BoxedTool, Tool},
+};
 
 pub struct ToolRegistration {
     constructor: fn() -> BoxedTool,
@@ -19,3 +23,57 @@ impl ToolRegistration {
 }
 
 inventory::collect!(ToolRegistration);
+
+pub struct ResourceRegistration {
+    constructor: fn() -> BoxedResource,
+}
+
+impl ResourceRegistration {
+    pub const fn new<T: Resource + Default + Send + Sync + 'static>() -> Self {
+        Self {
+            constructor: || Box::new(T::default()),
+        }
+    }
+
+    pub fn resource(&self) -> BoxedResource {
+        (self.constructor)()
+    }
+}
+
+inventory::collect!(ResourceRegistration);
+
+pub struct ResourceTemplateRegistration {
+    constructor: fn() -> BoxedResourceTemplate,
+}
+
+impl ResourceTemplateRegistration {
+    pub const fn new<T: ResourceTemplate + Default + Send + Sync + 'static>() -> Self {
+        Self {
+            constructor: || Box::new(T::default()),
+        }
+    }
+
+    pub fn resource_template(&self) -> BoxedResourceTemplate {
+        (self.constructor)()
+    }
+}
+
+inventory::collect!(ResourceTemplateRegistration);
+
+pub struct PromptRegistration {
+    constructor: fn() -> BoxedPrompt,
+}
+
+impl PromptRegistration {
+    pub const fn new<T: Prompt + Default + Send + Sync + 'static>() -> Self {
+        Self {
+            constructor: || Box::new(T::default()),
+        }
+    }
+
+    pub fn prompt(&self) -> BoxedPrompt {
+        (self.constructor)()
+    }
+}
+
+inventory::collect!(PromptRegistration);
@@ -1,74 +1,263 @@
+pub mod error;
 pub mod inventory;
+pub mod logging;
+pub mod middleware;
 pub mod primitives;
+pub mod roots;
 mod runtime;
+pub mod sampling;
+pub mod session;
+pub mod shutdown;
+pub mod version;
 
-use anyhow::{Context as _, anyhow};
+#[cfg(test)]
+mod test_utils;
+
+use anyhow::anyhow;
+use futures::future;
+use indexmap::IndexMap;
 use mmcp_protocol::{
     ProtocolVersion,
     mcp::{
-        self, CallToolResult, Implementation, InitializeRequest, InitializeResult, JSONRPCMessage,
-        RequestId, ServerCapabilities, ServerCapabilitiesPrompts, ServerCapabilitiesResources,
+        self, CallToolResult, GetPromptRequest, GetPromptResult, Implementation, InitializeRequest,
+        InitializeResult, JSONRPCMessage, ReadResourceRequest, ReadResourceResult, RequestId,
+        ServerCapabilities, ServerCapabilitiesPrompts, ServerCapabilitiesResources,
         ServerCapabilitiesTools,
     },
     port::{RPCPort, RPCSink},
 };
-use std::{borrow::Cow, collections::BTreeMap};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    sync::OnceLock,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tower::{BoxError, Layer, Service};
 
 use crate::{
-    inventory::ToolRegistration,
-    primitives::tool::{BoxedTool, Tool},
+    error::McpError,
+    inventory::{
+        PromptRegistration, ResourceRegistration, ResourceTemplateRegistration, ToolRegistration,
+    },
+    logging::LoggingLayer,
+    middleware::{Layers, PromptService, ResourceService, Stacks, ToolCall, ToolService, layer_fn},
+    primitives::{
+        prompt::{BoxedPrompt, Prompt},
+        resource::{
+            BoxedResource, BoxedResourceTemplate, Resource, ResourceNotifier, ResourceTemplate,
+            uri_template::UriTemplate,
+        },
+        tool::{SharedTool, States, Tool, ToolRegistry},
+    },
     runtime::MCPServerRuntime,
+    session::{Peer, Session, Sessions},
+    shutdown::{DEFAULT_SHUTDOWN_TIMEOUT, ShutdownSummary},
+    version::VersionPolicy,
 };
 
 /// MCP Server implementation with builder pattern for configuration
 pub struct MCPServer {
     name: String,
     version: String,
-    tools: BTreeMap<Cow<'static, str>, BoxedTool>,
+    tools: ToolRegistry,
+    resources: BTreeMap<Cow<'static, str>, BoxedResource>,
+    resource_templates: Vec<(UriTemplate, BoxedResourceTemplate)>,
+    prompts: BTreeMap<Cow<'static, str>, BoxedPrompt>,
     instructions: Option<String>,
+    capabilities: Option<ServerCapabilities>,
+    version_policy: VersionPolicy,
+    shutdown_timeout: Duration,
+    experimental_capabilities: IndexMap<String, serde_json::Map<String, serde_json::Value>>,
+    /// Whether a [`ToolRegistry`] was handed out, so that tools may change after initialize
+    dynamic_tools: AtomicBool,
+    /// Whether a [`LoggingLayer`] was handed out, so that `logging/setLevel` has an effect
+    logging: AtomicBool,
+    sessions: Sessions,
+    states: Arc<States>,
+    layers: Layers,
+    /// Built from `layers` when the server starts serving
+    stacks: OnceLock<Stacks>,
 }
 
 impl MCPServer {
     pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        let sessions = Sessions::default();
         Self {
             name: name.into(),
             version: version.into(),
-            tools: Default::default(),
+            tools: ToolRegistry::new(sessions.clone()),
+            resources: Default::default(),
+            resource_templates: Default::default(),
+            prompts: Default::default(),
             instructions: None,
+            capabilities: None,
+            version_policy: Default::default(),
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            experimental_capabilities: Default::default(),
+            dynamic_tools: AtomicBool::new(false),
+            logging: AtomicBool::new(false),
+            sessions,
+            states: Default::default(),
+            layers: Default::default(),
+            stacks: OnceLock::new(),
         }
     }
 
-    pub fn with_tools_from_inventory(mut self) -> Self {
+    pub fn with_tools_from_inventory(self) -> Self {
         for tool in inventory::iter::<ToolRegistration> {
-            let tool = tool.tool();
-            self.tools.insert(tool.name(), tool);
+            self.tools.insert(tool.tool().into());
         }
         self
     }
 
-    pub fn add_tool(mut self, tool: impl Tool + Send + Sync + 'static) -> Self {
-        self.tools.insert(tool.name(), Box::new(tool));
+    pub fn add_tool(self, tool: impl Tool + Send + Sync + 'static) -> Self {
+        self.tools.insert(Arc::new(tool));
         self
     }
 
-    pub fn get_tool(&self, name: &str) -> Option<&BoxedTool> {
+    pub fn get_tool(&self, name: &str) -> Option<SharedTool> {
         self.tools.get(name)
     }
 
-    pub fn list_tools(&self) -> impl Iterator<Item = &BoxedTool> {
-        self.tools.values()
+    pub fn list_tools(&self) -> Vec<SharedTool> {
+        self.tools.list()
+    }
+
+    /// Get a handle for adding and removing tools while the server is running.
+    ///
+    /// Taking the handle makes the server advertise `tools.listChanged` to clients.
+    pub fn tool_registry(&self) -> ToolRegistry {
+        self.dynamic_tools.store(true, Ordering::Relaxed);
+        self.tools.clone()
+    }
+
+    /// Add all resources and resource templates registered with `#[resource]`.
+    pub fn with_resources_from_inventory(mut self) -> Self {
+        for resource in inventory::iter::<ResourceRegistration> {
+            let resource = resource.resource();
+            self.resources.insert(resource.uri(), resource);
+        }
+        for template in inventory::iter::<ResourceTemplateRegistration> {
+            self.insert_resource_template(template.resource_template());
+        }
+        self
+    }
+
+    pub fn add_resource(mut self, resource: impl Resource + Send + Sync + 'static) -> Self {
+        self.resources.insert(resource.uri(), Box::new(resource));
+        self
+    }
+
+    pub fn get_resource(&self, uri: &str) -> Option<&BoxedResource> {
+        self.resources.get(uri)
     }
 
     /// List the resources available on this server.
-    pub fn list_resources(&self) -> impl Iterator<Item = mcp::Resource> {
-        // Currently, we don't have any resources, so return an empty iterator
-        std::iter::empty()
+    pub fn list_resources(&self) -> impl Iterator<Item = &BoxedResource> {
+        self.resources.values()
+    }
+
+    /// Get a handle for notifying subscribed clients that a resource has changed.
+    ///
+    /// The handle stays valid after the server is started, so it can be moved into a task that
+    /// watches the underlying data.
+    pub fn resource_notifier(&self) -> ResourceNotifier {
+        ResourceNotifier::new(self.sessions.clone())
+    }
+
+    /// Add a resource template. `resources/read` requests whose URI matches no static resource are
+    /// matched against the templates in the order they were added.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the URI template of the resource template is not a valid RFC 6570 template.
+    pub fn add_resource_template(
+        mut self,
+        template: impl ResourceTemplate + Send + Sync + 'static,
+    ) -> Self {
+        self.insert_resource_template(Box::new(template));
+        self
+    }
+
+    fn insert_resource_template(&mut self, template: BoxedResourceTemplate) {
+        let uri_template = UriTemplate::parse(&template.uri_template())
+            .unwrap_or_else(|e| panic!("invalid resource template: {e}"));
+        self.resource_templates.push((uri_template, template));
+    }
+
+    /// Find the first resource template matching the URI, along with the extracted variables.
+    pub fn match_resource_template(
+        &self,
+        uri: &str,
+    ) -> Option<(&BoxedResourceTemplate, BTreeMap<String, String>)> {
+        self.resource_templates
+            .iter()
+            .find_map(|(uri_template, template)| {
+                uri_template
+                    .match_uri(uri)
+                    .map(|variables| (template, variables))
+            })
+    }
+
+    /// List the resource templates available on this server.
+    pub fn list_resource_templates(&self) -> impl Iterator<Item = &BoxedResourceTemplate> {
+        self.resource_templates.iter().map(|(_, template)| template)
+    }
+
+    /// Add all prompts registered with `#[prompt]`.
+    pub fn with_prompts_from_inventory(mut self) -> Self {
+        for prompt in inventory::iter::<PromptRegistration> {
+            let prompt = prompt.prompt();
+            self.prompts.insert(prompt.name(), prompt);
+        }
+        self
+    }
+
+    pub fn add_prompt(mut self, prompt: impl Prompt + Send + Sync + 'static) -> Self {
+        self.prompts.insert(prompt.name(), Box::new(prompt));
+        self
+    }
+
+    pub fn get_prompt(&self, name: &str) -> Option<&BoxedPrompt> {
+        self.prompts.get(name)
     }
 
     /// List the prompts available on this server.
-    pub fn list_prompts(&self) -> impl Iterator<Item = mcp::Prompt> {
-        // Currently, we don't have any prompts, so return an empty iterator
-        std::iter::empty()
+    pub fn list_prompts(&self) -> impl Iterator<Item = &BoxedPrompt> {
+        self.prompts.values()
+    }
+
+    /// Whether any prompt argument or resource template variable has a completer, in which case
+    /// the `completions` capability is advertised.
+    pub fn has_completions(&self) -> bool {
+        let prompts = self.prompts.values().any(|prompt| {
+            prompt
+                .arguments()
+                .iter()
+                .any(|argument| prompt.completer(&argument.name).is_some())
+        });
+        let resource_templates = self
+            .resource_templates
+            .iter()
+            .any(|(uri_template, template)| {
+                uri_template
+                    .variables()
+                    .any(|variable| template.completer(variable).is_some())
+            });
+        prompts || resource_templates
+    }
+
+    /// Get a [`tracing_subscriber::Layer`] forwarding tracing events to the connected clients as
+    /// `notifications/message`.
+    ///
+    /// Taking the layer makes the server advertise the `logging` capability to clients.
+    pub fn logging_layer(&self) -> LoggingLayer {
+        self.logging.store(true, Ordering::Relaxed);
+        LoggingLayer::new(self.sessions.clone())
     }
 
     /// Set the instructions for the server which will be sent to the client on initialize.
@@ -77,23 +266,211 @@ impl MCPServer {
         self
     }
 
-    /// Start the server by transforming into runtime, then run
+    /// Set how the protocol version of each session is picked. By default, every version known
+    /// to this crate is accepted and unknown ones are answered with the latest.
+    pub fn with_version_policy(mut self, policy: VersionPolicy) -> Self {
+        self.version_policy = policy;
+        self
+    }
+
+    /// Add a value that tools can get by its type, with a [`State`] parameter in a `#[tool]` fn or
+    /// [`ToolContext::state`]. Adding a value of the same type replaces it.
+    ///
+    /// [`State`]: primitives::tool::State
+    /// [`ToolContext::state`]: primitives::tool::ToolContext::state
+    pub fn with_state<S: Send + Sync + 'static>(mut self, state: S) -> Self {
+        Arc::make_mut(&mut self.states).insert(state);
+        self
+    }
+
+    /// Wrap the execution of every tool in a [`tower::Layer`], for concerns such as timeouts,
+    /// concurrency limits, authorization and metrics.
+    ///
+    /// Layers added later wrap the earlier ones. The stack is built once when the server starts,
+    /// and cloned for each call, so layers like rate limits that cannot share their state through
+    /// clones need a `BufferLayer` in front of them. Errors returned by the layers are answered
+    /// with an internal error, unless they are an [`McpError`].
+    ///
+    /// ```ignore
+    /// let server = MCPServer::new("my-server", "1.0.0")
+    ///     .with_tools_from_inventory()
+    ///     .layer(TimeoutLayer::new(Duration::from_secs(30)))
+    ///     .layer(GlobalConcurrencyLimitLayer::new(8));
+    /// ```
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<ToolService> + Send + Sync + 'static,
+        L::Service: Service<ToolCall, Response = CallToolResult> + Clone + Send + Sync + 'static,
+        <L::Service as Service<ToolCall>>::Error: Into<BoxError>,
+        <L::Service as Service<ToolCall>>::Future: Send + 'static,
+    {
+        self.layers.tools.push(layer_fn(layer));
+        self
+    }
+
+    /// Wrap the reading of every resource and resource template in a [`tower::Layer`]. See
+    /// [`layer`](Self::layer).
+    pub fn resource_layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<ResourceService> + Send + Sync + 'static,
+        L::Service: Service<ReadResourceRequest, Response = ReadResourceResult>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        <L::Service as Service<ReadResourceRequest>>::Error: Into<BoxError>,
+        <L::Service as Service<ReadResourceRequest>>::Future: Send + 'static,
+    {
+        self.layers.resources.push(layer_fn(layer));
+        self
+    }
+
+    /// Wrap the rendering of every prompt in a [`tower::Layer`]. See [`layer`](Self::layer).
+    pub fn prompt_layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<PromptService> + Send + Sync + 'static,
+        L::Service:
+            Service<GetPromptRequest, Response = GetPromptResult> + Clone + Send + Sync + 'static,
+        <L::Service as Service<GetPromptRequest>>::Error: Into<BoxError>,
+        <L::Service as Service<GetPromptRequest>>::Future: Send + 'static,
+    {
+        self.layers.prompts.push(layer_fn(layer));
+        self
+    }
+
+    /// Set how long in-flight requests are waited for on shutdown before they are cancelled.
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Advertise an experimental, non-standard capability.
+    pub fn with_experimental_capability(
+        mut self,
+        name: impl Into<String>,
+        capability: serde_json::Map<String, serde_json::Value>,
+    ) -> Self {
+        self.experimental_capabilities
+            .insert(name.into(), capability);
+        self
+    }
+
+    /// Set the capabilities sent to the client on initialize, instead of the ones derived from the
+    /// registered primitives.
+    pub fn with_capabilities(mut self, capabilities: ServerCapabilities) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    /// The capabilities sent to the client on initialize.
+    ///
+    /// Unless set with [`with_capabilities`](Self::with_capabilities), they are derived from the
+    /// server: each primitive is advertised only if any is registered, `listChanged` only if the
+    /// server can notify changes of the list, and `logging` only if the
+    /// [logging layer](Self::logging_layer) was taken.
+    pub fn capabilities(&self) -> ServerCapabilities {
+        if let Some(capabilities) = &self.capabilities {
+            return capabilities.clone();
+        }
+        let dynamic_tools = self.dynamic_tools.load(Ordering::Relaxed);
+        let has_tools = dynamic_tools || !self.tools.list().is_empty();
+        let has_resources = !self.resources.is_empty() || !self.resource_templates.is_empty();
+        ServerCapabilities {
+            completions: self.has_completions().then(Default::default),
+            experimental: (!self.experimental_capabilities.is_empty())
+                .then(|| self.experimental_capabilities.clone()),
+            logging: self.logging.load(Ordering::Relaxed).then(Default::default),
+            prompts: (!self.prompts.is_empty()).then(|| ServerCapabilitiesPrompts {
+                list_changed: None,
+                extra: Default::default(),
+            }),
+            resources: has_resources.then(|| ServerCapabilitiesResources {
+                list_changed: None,
+                subscribe: Some(true),
+                extra: Default::default(),
+            }),
+            tools: has_tools.then(|| ServerCapabilitiesTools {
+                list_changed: dynamic_tools.then_some(true),
+                extra: Default::default(),
+            }),
+            extra: Default::default(),
+        }
+    }
+
+    /// Start the server by transforming into runtime, then run until the connection is closed.
+    ///
+    /// Requests still in flight when the connection closes are cancelled right away and answered
+    /// with an error, rather than waited for. Use
+    /// [`start_with_shutdown`](Self::start_with_shutdown) to wait for them.
     pub async fn start<P: RPCPort>(self, port: P) -> anyhow::Result<()> {
-        let sink = port.sink();
-        let runtime = MCPServerRuntime::new(self, sink.clone());
-        runtime.run(port).await
+        Arc::new(self).serve(port).await
+    }
+
+    /// Start the server, and shut it down gracefully when `shutdown` completes.
+    ///
+    /// On shutdown, new requests are answered with an error, in-flight requests are waited for
+    /// until the [shutdown timeout](Self::with_shutdown_timeout), and the port is closed so that
+    /// every response is delivered before this returns. In-flight requests are waited for the
+    /// same way when the connection closes.
+    ///
+    /// ```ignore
+    /// let token = CancellationToken::new();
+    /// let summary = server
+    ///     .start_with_shutdown(stdio_server_rpc(), token.clone().cancelled_owned())
+    ///     .await?;
+    /// ```
+    pub async fn start_with_shutdown<P: RPCPort>(
+        self,
+        port: P,
+        shutdown: impl Future<Output = ()>,
+    ) -> anyhow::Result<ShutdownSummary> {
+        Arc::new(self).serve_with_shutdown(port, shutdown).await
+    }
+
+    /// Run a session over the port on a shared server, for transports accepting multiple
+    /// connections such as HTTP. Like [`start`](Self::start), requests still in flight when the
+    /// connection closes are cancelled right away.
+    pub async fn serve<P: RPCPort>(self: Arc<Self>, port: P) -> anyhow::Result<()> {
+        self.run_session(port, future::pending(), Duration::ZERO)
+            .await?;
+        Ok(())
+    }
+
+    /// Run a session over the port on a shared server, shutting it down gracefully when
+    /// `shutdown` completes. See [`start_with_shutdown`](Self::start_with_shutdown).
+    pub async fn serve_with_shutdown<P: RPCPort>(
+        self: Arc<Self>,
+        port: P,
+        shutdown: impl Future<Output = ()>,
+    ) -> anyhow::Result<ShutdownSummary> {
+        let drain_timeout = self.shutdown_timeout;
+        self.run_session(port, shutdown, drain_timeout).await
+    }
+
+    async fn run_session<P: RPCPort>(
+        self: Arc<Self>,
+        port: P,
+        shutdown: impl Future<Output = ()>,
+        drain_timeout: Duration,
+    ) -> anyhow::Result<ShutdownSummary> {
+        if !self.layers.is_empty() {
+            self.stacks.get_or_init(|| Stacks::new(&self));
+        }
+        let peer = Peer::new(port.sink());
+        let runtime = MCPServerRuntime::new(self, peer).with_drain_timeout(drain_timeout);
+        runtime.run(port, shutdown).await
     }
 
     /// Handle the initialization process
-    async fn initialize<P: RPCPort, S: RPCSink>(
+    async fn initialize<P: RPCPort>(
         &self,
         port: &mut P,
-        sink: &mut S,
+        session: &Session,
     ) -> anyhow::Result<Vec<JSONRPCMessage>> {
         let mut queued_messages = Vec::new();
 
-        // Step 1: Wait for initialize request
-        let (init_request_id, init_request) = loop {
+        // Step 1: Wait for an initialize request with an acceptable protocol version
+        loop {
             let message = port
                 .progress()
                 .await?
@@ -101,24 +478,64 @@ impl MCPServer {
 
             match message {
                 JSONRPCMessage::JSONRPCRequest(request) if request.method == "initialize" => {
-                    // Parse the initialize request
+                    // Parse the initialize request, or reject it and wait for another
+                    let mut sink = session.peer().clone();
                     let request_value = serde_json::to_value(&request)
                         .map_err(|e| anyhow!("failed to serialize request: {}", e))?;
-                    let initialize_request: InitializeRequest =
-                        serde_json::from_value(request_value)
-                            .map_err(|e| anyhow!("failed to parse initialize request: {}", e))?;
+                    let init_request: InitializeRequest =
+                        match serde_json::from_value(request_value) {
+                            Ok(init_request) => init_request,
+                            Err(e) => {
+                                let error = McpError::invalid_params(format!(
+                                    "Invalid initialize request: {e}"
+                                ));
+                                sink.send_message(JSONRPCMessage::JSONRPCError(
+                                    error.into_response(request.id),
+                                ))
+                                .await?;
+                                continue;
+                            }
+                        };
 
-                    // Return the request ID and request
-                    break (request.id.clone(), initialize_request);
+                    // Step 2: Respond to initialize request, or reject it and wait for another
+                    match self
+                        .version_policy
+                        .negotiate(&init_request.params.protocol_version)
+                    {
+                        Ok(protocol_version) => {
+                            let capabilities = self.capabilities();
+                            session.set_client(
+                                init_request.params.client_info,
+                                init_request.params.capabilities,
+                                protocol_version,
+                                capabilities.clone(),
+                            );
+                            self.send_initialize_response(
+                                &mut sink,
+                                request.id,
+                                protocol_version,
+                                capabilities,
+                            )
+                            .await?;
+                            break;
+                        }
+                        Err(error) => {
+                            let error = McpError::invalid_params("Unsupported protocol version")
+                                .with_data(serde_json::json!({
+                                    "supported": error.supported.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                                    "requested": error.requested,
+                                }));
+                            sink.send_message(JSONRPCMessage::JSONRPCError(
+                                error.into_response(request.id),
+                            ))
+                            .await?;
+                        }
+                    }
                 }
                 // Queue any other messages to be processed after initialization
                 _ => queued_messages.push(message),
             }
-        };
-
-        // Step 2: Respond to initialize request
-        self.send_initialize_response(sink, init_request_id, &init_request)
-            .await?;
+        }
 
         // Step 3: Wait for initialized notification
         loop {
@@ -132,6 +549,7 @@ impl MCPServer {
                     if notification.method == "notifications/initialized" =>
                 {
                     // Initialized notification received, initialization is complete
+                    session.set_initialized();
                     break;
                 }
                 // Queue any other messages to be processed after initialization
@@ -147,31 +565,12 @@ impl MCPServer {
         &self,
         sink: &mut S,
         id: RequestId,
-        init_request: &InitializeRequest,
+        protocol_version: ProtocolVersion,
+        capabilities: ServerCapabilities,
     ) -> anyhow::Result<()> {
-        let protocol_version = init_request
-            .params
-            .protocol_version
-            .parse::<ProtocolVersion>()
-            .context("failed to parse protocol version")?;
         let response = InitializeResult {
             meta: None,
-            capabilities: ServerCapabilities {
-                tools: Some(ServerCapabilitiesTools {
-                    list_changed: Some(true),
-                    extra: Default::default(),
-                }),
-                resources: Some(ServerCapabilitiesResources {
-                    list_changed: Some(true),
-                    subscribe: Some(false),
-                    extra: Default::default(),
-                }),
-                prompts: Some(ServerCapabilitiesPrompts {
-                    list_changed: Some(true),
-                    extra: Default::default(),
-                }),
-                ..Default::default()
-            },
+            capabilities,
             instructions: self.instructions.clone(),
             protocol_version: protocol_version.to_string(),
             server_info: Implementation {
@@ -186,12 +585,31 @@ impl MCPServer {
     }
 }
 
-fn serialize_tool_call_result(result: CallToolResult) -> anyhow::Result<mcp::Result> {
+fn serialize_result<T: serde::Serialize>(result: T) -> anyhow::Result<mcp::Result> {
     let serde_json::Value::Object(result) = serde_json::to_value(&result)? else {
-        panic!("CallToolResult should be serialized to an object");
+        return Err(anyhow!("result should be serialized to an object"));
     };
     Ok(mcp::Result {
         meta: Default::default(),
         extra: result,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let server = MCPServer::new("test", "0.1.0");
+        let capabilities = server.capabilities();
+        assert!(capabilities.logging.is_none());
+        assert!(capabilities.tools.is_none());
+
+        let _layer = server.logging_layer();
+        let _registry = server.tool_registry();
+        let capabilities = server.capabilities();
+        assert!(capabilities.logging.is_some());
+        assert_eq!(capabilities.tools.unwrap().list_changed, Some(true));
+    }
+}
@@ -0,0 +1,158 @@
+use mmcp_protocol::mcp::{LoggingLevel, LoggingMessageNotificationParams};
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_subscriber::{Layer, layer::Context};
+
+use crate::session::Sessions;
+
+/// A [`Layer`] sending tracing events to the connected clients as `notifications/message`.
+///
+/// Each session only receives events at or above the level it set with `logging/setLevel`, and
+/// nothing before that. Messages are sent in order by a task on the current tokio runtime, and
+/// events emitted outside of one, or while too many messages are waiting, are dropped.
+pub struct LoggingLayer {
+    sessions: Sessions,
+}
+
+impl LoggingLayer {
+    pub(crate) fn new(sessions: Sessions) -> Self {
+        Self { sessions }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LoggingLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let level = logging_level(event.metadata().level());
+        let sessions = self
+            .sessions
+            .list()
+            .into_iter()
+            .filter(|session| session.is_log_enabled(&level))
+            .collect::<Vec<_>>();
+        if sessions.is_empty() {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let mut visitor = JsonVisitor::default();
+        event.record(&mut visitor);
+        let params = LoggingMessageNotificationParams {
+            data: serde_json::Value::Object(visitor.0),
+            level,
+            logger: Some(event.metadata().target().to_string()),
+            extra: Default::default(),
+        };
+        for session in sessions {
+            session.queue_log(&runtime, params.clone());
+        }
+    }
+}
+
+fn logging_level(level: &Level) -> LoggingLevel {
+    match *level {
+        Level::ERROR => LoggingLevel::Error,
+        Level::WARN => LoggingLevel::Warning,
+        Level::INFO => LoggingLevel::Info,
+        Level::DEBUG | Level::TRACE => LoggingLevel::Debug,
+    }
+}
+
+/// The RFC 5424 severity of the level, ordered from the least severe.
+pub(crate) fn severity(level: &LoggingLevel) -> u8 {
+    match level {
+        LoggingLevel::Debug => 0,
+        LoggingLevel::Info => 1,
+        LoggingLevel::Notice => 2,
+        LoggingLevel::Warning => 3,
+        LoggingLevel::Error => 4,
+        LoggingLevel::Critical => 5,
+        LoggingLevel::Alert => 6,
+        LoggingLevel::Emergency => 7,
+    }
+}
+
+/// Collects the fields of an event, including `message`, into a JSON object.
+#[derive(Default)]
+struct JsonVisitor(serde_json::Map<String, serde_json::Value>);
+
+impl Visit for JsonVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}").into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    use crate::{MCPServer, test_utils::TestClient};
+
+    // Multiple threads, so that messages sent concurrently would be reordered
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_logging_layer() {
+        let server = MCPServer::new("test", "0.1.0");
+        let subscriber = tracing_subscriber::registry().with(server.logging_layer());
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let mut client = TestClient::serve(server);
+        let result = client.initialize("2025-03-26", json!({})).await;
+        assert_eq!(result["capabilities"]["logging"], json!({}));
+
+        // Nothing is sent before the client sets a level
+        tracing::error!(target: "test", "before setLevel");
+        let response = client
+            .request(
+                json!({"jsonrpc": "2.0", "id": 2, "method": "logging/setLevel", "params": {
+                    "level": "info"
+                }}),
+            )
+            .await;
+        assert_eq!(response["id"], 2);
+
+        for n in 0..100 {
+            tracing::debug!(target: "test", n, "filtered");
+            tracing::info!(target: "test", n, "sent");
+        }
+        tracing::warn!(target: "test", "done");
+
+        let mut messages = Vec::new();
+        while messages.len() < 101 {
+            let notification = client.next().await;
+            assert_eq!(notification["method"], "notifications/message");
+            if notification["params"]["logger"] == "test" {
+                messages.push(notification["params"].clone());
+            }
+        }
+        for (n, message) in messages[..100].iter().enumerate() {
+            assert_eq!(message["level"], "info");
+            assert_eq!(message["data"], json!({"message": "sent", "n": n}));
+        }
+        assert_eq!(messages[100]["level"], "warning");
+        assert_eq!(messages[100]["data"]["message"], "done");
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::{Arc, Weak};
+
+use mmcp_protocol::mcp::{
+    CallToolRequest, CallToolResult, GetPromptRequest, GetPromptResult, ReadResourceRequest,
+    ReadResourceResult,
+};
+use tower::{BoxError, Layer, Service, ServiceExt as _, service_fn, util::BoxCloneSyncService};
+
+use crate::{MCPServer, error::McpError, primitives::tool::ToolContext};
+
+/// A `tools/call` request passed through the layers added with [`MCPServer::layer`].
+pub struct ToolCall {
+    pub request: CallToolRequest,
+    pub context: ToolContext,
+}
+
+/// The service executing tools, wrapped by [`MCPServer::layer`].
+pub type ToolService = BoxCloneSyncService<ToolCall, CallToolResult, BoxError>;
+/// The service reading resources, wrapped by [`MCPServer::resource_layer`].
+pub type ResourceService = BoxCloneSyncService<ReadResourceRequest, ReadResourceResult, BoxError>;
+/// The service rendering prompts, wrapped by [`MCPServer::prompt_layer`].
+pub type PromptService = BoxCloneSyncService<GetPromptRequest, GetPromptResult, BoxError>;
+
+type LayerFn<S> = Box<dyn Fn(S) -> S + Send + Sync>;
+
+/// Box a layer so that it can be applied once the server is shared.
+pub(crate) fn layer_fn<L, T, U>(layer: L) -> LayerFn<BoxCloneSyncService<T, U, BoxError>>
+where
+    L: Layer<BoxCloneSyncService<T, U, BoxError>> + Send + Sync + 'static,
+    L::Service: Service<T, Response = U> + Clone + Send + Sync + 'static,
+    <L::Service as Service<T>>::Error: Into<BoxError>,
+    <L::Service as Service<T>>::Future: Send + 'static,
+    T: 'static,
+    U: 'static,
+{
+    Box::new(move |service| BoxCloneSyncService::new(layer.layer(service).map_err(Into::into)))
+}
+
+/// The layers added to the server, applied in the order they were added, so the last one is the
+/// outermost.
+#[derive(Default)]
+pub(crate) struct Layers {
+    pub(crate) tools: Vec<LayerFn<ToolService>>,
+    pub(crate) resources: Vec<LayerFn<ResourceService>>,
+    pub(crate) prompts: Vec<LayerFn<PromptService>>,
+}
+
+impl Layers {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.tools.is_empty() && self.resources.is_empty() && self.prompts.is_empty()
+    }
+}
+
+/// The layered services, built once per server so that layers like concurrency limits share
+/// their state across sessions.
+pub(crate) struct Stacks {
+    pub(crate) tools: ToolService,
+    pub(crate) resources: ResourceService,
+    pub(crate) prompts: PromptService,
+}
+
+impl Stacks {
+    /// Build the stacks around services calling back into the server. They hold a weak reference,
+    /// since the server owns them.
+    pub(crate) fn new(server: &Arc<MCPServer>) -> Self {
+        let weak = Arc::downgrade(server);
+        let tools = ToolService::new(service_fn(move |call: ToolCall| {
+            let server = upgrade(&weak);
+            async move { Ok(server?.call_tool(call.request, call.context).await?) }
+        }));
+        let weak = Arc::downgrade(server);
+        let resources = ResourceService::new(service_fn(move |request| {
+            let server = upgrade(&weak);
+            async move { Ok(server?.read_resource(request).await?) }
+        }));
+        let weak = Arc::downgrade(server);
+        let prompts = PromptService::new(service_fn(move |request| {
+            let server = upgrade(&weak);
+            async move { Ok(server?.render_prompt(request).await?) }
+        }));
+
+        let layers = &server.layers;
+        Self {
+            tools: layers
+                .tools
+                .iter()
+                .fold(tools, |service, layer| layer(service)),
+            resources: layers
+                .resources
+                .iter()
+                .fold(resources, |service, layer| layer(service)),
+            prompts: layers
+                .prompts
+                .iter()
+                .fold(prompts, |service, layer| layer(service)),
+        }
+    }
+}
+
+fn upgrade(server: &Weak<MCPServer>) -> Result<Arc<MCPServer>, McpError> {
+    server
+        .upgrade()
+        .ok_or_else(|| McpError::internal("Server is shutting down"))
+}
+
+/// Call the service, answering errors from layers that are not an [`McpError`], such as
+/// timeouts, with an internal error.
+pub(crate) async fn call<S, T, U>(service: &S, request: T) -> Result<U, McpError>
+where
+    S: Service<T, Response = U, Error = BoxError> + Clone,
+{
+    service
+        .clone()
+        .oneshot(request)
+        .await
+        .map_err(|error| match error.downcast::<McpError>() {
+            Ok(error) => *error,
+            Err(error) => McpError::internal(error.to_string()),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use mmcp_protocol::consts::error_codes;
+    use tower::{layer::layer_fn, timeout::TimeoutLayer};
+
+    use super::*;
+    use crate::test_utils::{TestClient, call_tool, sleep};
+
+    #[tokio::test]
+    async fn test_tool_layers() {
+        let server = MCPServer::new("test", "0.1.0")
+            .add_tool(sleep("0"))
+            .add_tool(sleep("1"))
+            .add_tool(sleep("60000"))
+            .layer(TimeoutLayer::new(Duration::from_millis(100)))
+            .layer(layer_fn(|inner: ToolService| {
+                service_fn(move |call: ToolCall| {
+                    let inner = inner.clone();
+                    async move {
+                        if call.request.params.name == "1" {
+                            return Err(McpError::invalid_request("Forbidden").into());
+                        }
+                        inner.oneshot(call).await
+                    }
+                })
+            }));
+        let mut client = TestClient::connect(server).await;
+
+        let response = client.request(call_tool(1, "0")).await;
+        assert_eq!(response["result"]["content"][0]["text"], "done");
+
+        let response = client.request(call_tool(2, "1")).await;
+        assert_eq!(response["error"]["code"], error_codes::INVALID_REQUEST);
+        assert_eq!(response["error"]["message"], "Forbidden");
+
+        let response = client.request(call_tool(3, "60000")).await;
+        assert_eq!(response["error"]["code"], error_codes::INTERNAL_ERROR);
+        assert_eq!(response["error"]["message"], "request timed out");
+
+        let response = client.request(call_tool(4, "missing")).await;
+        assert_eq!(response["error"]["code"], error_codes::INVALID_PARAMS);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod completion;
 pub mod prompt;
 pub mod resource;
+mod string_map;
 pub mod tool;
@@ -0,0 +1,214 @@
+use std::{future::Future, pin::Pin};
+
+use futures::FutureExt as _;
+
+use mmcp_protocol::mcp::CompleteResultCompletion;
+
+/// The maximum number of values a single `completion/complete` response may carry.
+pub const MAX_COMPLETION_VALUES: usize = 100;
+
+/// Suggests values for a prompt argument or a resource template variable.
+///
+/// Implemented for every `async fn(String) -> impl IntoCompletion`, which receives the value the
+/// user has typed so far.
+pub trait Completer {
+    fn complete(
+        &self,
+        value: String,
+    ) -> Pin<Box<dyn Future<Output = CompleteResultCompletion> + Send + '_>>;
+}
+
+impl<F, Fut, O> Completer for F
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = O> + Send + 'static,
+    O: IntoCompletion + 'static,
+{
+    fn complete(
+        &self,
+        value: String,
+    ) -> Pin<Box<dyn Future<Output = CompleteResultCompletion> + Send + '_>> {
+        Box::pin(self(value).map(IntoCompletion::into_completion))
+    }
+}
+
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` must implement `IntoCompletion`",
+    note = "Return `Vec<String>` with the candidate values, or `CompleteResultCompletion` to set `total` and `hasMore` yourself."
+)]
+pub trait IntoCompletion {
+    fn into_completion(self) -> CompleteResultCompletion;
+}
+
+/// Sent as is, except that values beyond [`MAX_COMPLETION_VALUES`] are cut with `hasMore` set,
+/// and `total` counting them if it was not set.
+impl IntoCompletion for CompleteResultCompletion {
+    fn into_completion(mut self) -> CompleteResultCompletion {
+        if self.values.len() > MAX_COMPLETION_VALUES {
+            self.total.get_or_insert(self.values.len() as i64);
+            self.values.truncate(MAX_COMPLETION_VALUES);
+            self.has_more = Some(true);
+        }
+        self
+    }
+}
+
+/// Candidate values. Only the first [`MAX_COMPLETION_VALUES`] are sent, with `total` and
+/// `hasMore` reporting the rest.
+impl IntoCompletion for Vec<String> {
+    fn into_completion(mut self) -> CompleteResultCompletion {
+        let total = self.len();
+        self.truncate(MAX_COMPLETION_VALUES);
+        CompleteResultCompletion {
+            has_more: Some(total > self.len()),
+            total: Some(total as i64),
+            values: self,
+            extra: Default::default(),
+        }
+    }
+}
+
+impl IntoCompletion for Vec<&str> {
+    fn into_completion(self) -> CompleteResultCompletion {
+        self.into_iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .into_completion()
+    }
+}
+
+pub(crate) fn empty_completion() -> CompleteResultCompletion {
+    Vec::<String>::new().into_completion()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::BTreeMap};
+
+    use mmcp_protocol::{consts::error_codes, mcp::Annotations};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{MCPServer, primitives::resource::TypedResourceTemplate, test_utils::TestClient};
+
+    /// The numbers from 0 to 149 starting with the value.
+    async fn complete_number(value: String) -> Vec<String> {
+        (0..150)
+            .map(|n| n.to_string())
+            .filter(|n| n.starts_with(&value))
+            .collect()
+    }
+
+    struct Numbers;
+
+    impl TypedResourceTemplate for Numbers {
+        type Variables = BTreeMap<String, String>;
+        type Output = String;
+
+        fn uri_template(&self) -> Cow<'static, str> {
+            "numbers://{n}".into()
+        }
+
+        fn name(&self) -> Cow<'static, str> {
+            "numbers".into()
+        }
+
+        fn description(&self) -> Option<Cow<'static, str>> {
+            None
+        }
+
+        fn mime_type(&self) -> Option<Cow<'static, str>> {
+            None
+        }
+
+        fn annotations(&self) -> Option<Annotations> {
+            None
+        }
+
+        fn completer(&self, variable: &str) -> Option<&(dyn Completer + Send + Sync)> {
+            match variable {
+                "n" => Some(&complete_number),
+                _ => None,
+            }
+        }
+
+        async fn read(&self, variables: Self::Variables) -> Self::Output {
+            variables["n"].clone()
+        }
+    }
+
+    fn complete(id: i64, uri: &str, argument: &str, value: &str) -> serde_json::Value {
+        json!({"jsonrpc": "2.0", "id": id, "method": "completion/complete", "params": {
+            "ref": {"type": "ref/resource", "uri": uri},
+            "argument": {"name": argument, "value": value}
+        }})
+    }
+
+    #[tokio::test]
+    async fn test_complete() {
+        let server = MCPServer::new("test", "0.1.0").add_resource_template(Numbers);
+        let mut client = TestClient::serve(server);
+        let result = client.initialize("2025-03-26", json!({})).await;
+        assert_eq!(result["capabilities"]["completions"], json!({}));
+
+        // Truncated to the first 100 values
+        let response = client.request(complete(1, "numbers://{n}", "n", "")).await;
+        let completion = &response["result"]["completion"];
+        assert_eq!(
+            completion["values"].as_array().unwrap().len(),
+            MAX_COMPLETION_VALUES
+        );
+        assert_eq!(completion["values"][99], "99");
+        assert_eq!(completion["total"], 150);
+        assert_eq!(completion["hasMore"], true);
+
+        let response = client
+            .request(complete(2, "numbers://{n}", "n", "14"))
+            .await;
+        assert_eq!(
+            response["result"]["completion"],
+            json!({
+                "values": ["14", "140", "141", "142", "143", "144", "145", "146", "147", "148", "149"],
+                "total": 11,
+                "hasMore": false
+            })
+        );
+
+        // Variables without a completer have no suggestions
+        let response = client.request(complete(3, "numbers://{n}", "m", "1")).await;
+        assert_eq!(
+            response["result"]["completion"],
+            json!({"values": [], "total": 0, "hasMore": false})
+        );
+
+        let response = client.request(complete(4, "unknown://{n}", "n", "1")).await;
+        assert_eq!(response["error"]["code"], error_codes::RESOURCE_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_complete_result_completion() {
+        let values = (0..150).map(|n| n.to_string()).collect::<Vec<_>>();
+        let completion = CompleteResultCompletion {
+            has_more: None,
+            total: None,
+            values: values.clone(),
+            extra: Default::default(),
+        }
+        .into_completion();
+        assert_eq!(completion.values, values[..MAX_COMPLETION_VALUES]);
+        assert_eq!(completion.total, Some(150));
+        assert_eq!(completion.has_more, Some(true));
+
+        // A total set by the completer is kept
+        let completion = CompleteResultCompletion {
+            has_more: Some(false),
+            total: Some(1000),
+            values,
+            extra: Default::default(),
+        }
+        .into_completion();
+        assert_eq!(completion.values.len(), MAX_COMPLETION_VALUES);
+        assert_eq!(completion.total, Some(1000));
+        assert_eq!(completion.has_more, Some(true));
+    }
+}
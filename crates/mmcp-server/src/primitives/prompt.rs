@@ -1 +1,393 @@
-pub trait Prompt {}
+use std::{borrow::Cow, future::Future, pin::Pin};
+
+use futures::FutureExt as _;
+
+use mmcp_protocol::mcp::{
+    AudioContent, EmbeddedResource, GetPromptRequest, GetPromptResult, ImageContent,
+    PromptArgument, PromptMessage, PromptMessageContent, Role, TextContent,
+};
+
+use crate::{
+    error::McpError,
+    primitives::{completion::Completer, string_map::from_string_map, tool::Text},
+};
+
+pub type BoxedPrompt = Box<dyn Prompt + Send + Sync + 'static>;
+
+pub trait Prompt {
+    /// The name of the prompt
+    fn name(&self) -> Cow<'static, str>;
+
+    /// The description of the prompt
+    fn description(&self) -> Option<Cow<'static, str>>;
+
+    /// The arguments the prompt accepts
+    fn arguments(&self) -> Vec<PromptArgument>;
+
+    /// The completer suggesting values for an argument, if any
+    fn completer(&self, argument: &str) -> Option<&(dyn Completer + Send + Sync)> {
+        let _ = argument;
+        None
+    }
+
+    /// Render the prompt with the given arguments
+    fn get(
+        &self,
+        request: GetPromptRequest,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<GetPromptResult>> + Send + '_>>;
+}
+
+pub trait TypedPrompt {
+    type Arguments;
+    type Output;
+
+    /// The name of the prompt
+    fn name(&self) -> Cow<'static, str>;
+
+    /// The description of the prompt
+    fn description(&self) -> Option<Cow<'static, str>>;
+
+    /// The arguments the prompt accepts, usually derived from `Self::Arguments` with
+    /// [`arguments_from_schema`]
+    fn arguments(&self) -> Vec<PromptArgument>;
+
+    /// The completer suggesting values for an argument, if any
+    fn completer(&self, argument: &str) -> Option<&(dyn Completer + Send + Sync)> {
+        let _ = argument;
+        None
+    }
+
+    /// Render the prompt with the given arguments
+    fn get(&self, arguments: Self::Arguments) -> impl Future<Output = Self::Output> + Send;
+}
+
+impl<T, A, O> Prompt for T
+where
+    T: TypedPrompt<Arguments = A, Output = O> + Sync,
+    A: serde::de::DeserializeOwned,
+    O: IntoPromptMessages,
+{
+    fn name(&self) -> Cow<'static, str> {
+        self.name()
+    }
+
+    fn description(&self) -> Option<Cow<'static, str>> {
+        self.description()
+    }
+
+    fn arguments(&self) -> Vec<PromptArgument> {
+        self.arguments()
+    }
+
+    fn completer(&self, argument: &str) -> Option<&(dyn Completer + Send + Sync)> {
+        self.completer(argument)
+    }
+
+    fn get(
+        &self,
+        request: GetPromptRequest,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<GetPromptResult>> + Send + '_>> {
+        let arguments = match from_string_map(request.params.arguments.unwrap_or_default()) {
+            Ok(arguments) => arguments,
+            Err(e) => {
+                let error = McpError::invalid_params(format!("Invalid prompt arguments: {e}"));
+                return Box::pin(async move { Err(error.into()) });
+            }
+        };
+        let description = self.description();
+        Box::pin(self.get(arguments).map(move |output| {
+            Ok(GetPromptResult {
+                description: description.map(Cow::into_owned),
+                messages: output.into_prompt_messages()?,
+                meta: None,
+                extra: Default::default(),
+            })
+        }))
+    }
+}
+
+/// Build the list of prompt arguments from the JSON schema of an arguments struct.
+///
+/// Each top-level property becomes an argument, with its `description` and whether it is listed
+/// in `required`.
+pub fn arguments_from_schema(schema: &serde_json::Value) -> Vec<PromptArgument> {
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .map(|required| required.iter().filter_map(|name| name.as_str()).collect())
+        .unwrap_or_default();
+    schema["properties"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, property)| PromptArgument {
+            name: name.clone(),
+            description: property["description"].as_str().map(ToString::to_string),
+            required: Some(required.contains(&name.as_str())),
+            extra: Default::default(),
+        })
+        .collect()
+}
+
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` must implement `IntoPromptMessages`",
+    note = "Wrap your type in `Text<{Self}>` if it implements `Display`."
+)]
+pub trait IntoPromptMessages {
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>>;
+}
+
+impl<T, E> IntoPromptMessages for Result<T, E>
+where
+    T: IntoPromptMessages,
+    E: std::fmt::Display,
+{
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        match self {
+            Ok(output) => output.into_prompt_messages(),
+            Err(error) => Err(anyhow::anyhow!("{}", error)),
+        }
+    }
+}
+
+impl<T> IntoPromptMessages for Vec<T>
+where
+    T: IntoPromptMessages,
+{
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        let mut messages = Vec::new();
+        for item in self {
+            messages.extend(item.into_prompt_messages()?);
+        }
+        Ok(messages)
+    }
+}
+
+macro_rules! impl_into_prompt_messages_for_tuple {
+    ($($name:ident),*) => {
+        impl<$($name),*> IntoPromptMessages for ($($name,)*)
+        where
+            $($name: IntoPromptMessages,)*
+        {
+            #[allow(non_snake_case)]
+            fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+                let ($($name,)*) = self;
+                let mut messages = Vec::new();
+                $(messages.extend($name.into_prompt_messages()?);)*
+                Ok(messages)
+            }
+        }
+    };
+}
+
+impl_into_prompt_messages_for_tuple!(A, B);
+impl_into_prompt_messages_for_tuple!(A, B, C);
+impl_into_prompt_messages_for_tuple!(A, B, C, D);
+
+impl IntoPromptMessages for PromptMessage {
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        Ok(vec![self])
+    }
+}
+
+impl IntoPromptMessages for PromptMessageContent {
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        Ok(vec![PromptMessage {
+            content: self,
+            role: Role::User,
+            extra: Default::default(),
+        }])
+    }
+}
+
+impl IntoPromptMessages for String {
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        TextContent {
+            text: self,
+            annotations: None,
+            r#type: Default::default(),
+            extra: Default::default(),
+        }
+        .into_prompt_messages()
+    }
+}
+
+impl IntoPromptMessages for &str {
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        self.to_string().into_prompt_messages()
+    }
+}
+
+impl<T> IntoPromptMessages for Text<T>
+where
+    T: std::fmt::Display,
+{
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        self.0.to_string().into_prompt_messages()
+    }
+}
+
+impl IntoPromptMessages for TextContent {
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        PromptMessageContent::TextContent(self).into_prompt_messages()
+    }
+}
+
+impl IntoPromptMessages for ImageContent {
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        PromptMessageContent::ImageContent(self).into_prompt_messages()
+    }
+}
+
+impl IntoPromptMessages for AudioContent {
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        PromptMessageContent::AudioContent(self).into_prompt_messages()
+    }
+}
+
+impl IntoPromptMessages for EmbeddedResource {
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        PromptMessageContent::EmbeddedResource(self).into_prompt_messages()
+    }
+}
+
+/// Messages sent with the `user` role. Bare values default to this role.
+pub struct User<T>(pub T);
+
+impl<T> IntoPromptMessages for User<T>
+where
+    T: IntoPromptMessages,
+{
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        with_role(self.0, Role::User)
+    }
+}
+
+/// Messages sent with the `assistant` role.
+pub struct Assistant<T>(pub T);
+
+impl<T> IntoPromptMessages for Assistant<T>
+where
+    T: IntoPromptMessages,
+{
+    fn into_prompt_messages(self) -> anyhow::Result<Vec<PromptMessage>> {
+        with_role(self.0, Role::Assistant)
+    }
+}
+
+fn with_role(messages: impl IntoPromptMessages, role: Role) -> anyhow::Result<Vec<PromptMessage>> {
+    Ok(messages
+        .into_prompt_messages()?
+        .into_iter()
+        .map(|message| PromptMessage {
+            role: role.clone(),
+            ..message
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use mmcp_protocol::consts::error_codes;
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{MCPServer, test_utils::TestClient};
+
+    struct Review;
+
+    #[derive(serde::Deserialize)]
+    struct ReviewArguments {
+        code: String,
+        lines: Option<u32>,
+    }
+
+    impl TypedPrompt for Review {
+        type Arguments = ReviewArguments;
+        type Output = (String, Assistant<&'static str>);
+
+        fn name(&self) -> Cow<'static, str> {
+            "review".into()
+        }
+
+        fn description(&self) -> Option<Cow<'static, str>> {
+            Some("Review a piece of code".into())
+        }
+
+        fn arguments(&self) -> Vec<PromptArgument> {
+            arguments_from_schema(&json!({
+                "type": "object",
+                "properties": {
+                    "code": {"type": "string", "description": "The code to review"},
+                    "lines": {"type": "integer"}
+                },
+                "required": ["code"]
+            }))
+        }
+
+        async fn get(&self, arguments: Self::Arguments) -> Self::Output {
+            let lines = arguments.lines.unwrap_or(10);
+            (
+                format!("Review `{}` in at most {lines} lines.", arguments.code),
+                Assistant("Sure."),
+            )
+        }
+    }
+
+    async fn get_prompt(client: &mut TestClient, name: &str, arguments: Value) -> Value {
+        client
+            .request(
+                json!({"jsonrpc": "2.0", "id": 2, "method": "prompts/get", "params": {
+                    "name": name, "arguments": arguments
+                }}),
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_get_prompt() {
+        let mut client =
+            TestClient::connect(MCPServer::new("test", "0.1.0").add_prompt(Review)).await;
+
+        let response = client
+            .request(json!({"jsonrpc": "2.0", "id": 1, "method": "prompts/list"}))
+            .await;
+        assert_eq!(
+            response["result"]["prompts"],
+            json!([{
+                "name": "review",
+                "description": "Review a piece of code",
+                "arguments": [
+                    {"name": "code", "description": "The code to review", "required": true},
+                    {"name": "lines", "required": false}
+                ]
+            }])
+        );
+
+        // Arguments are sent as strings and parsed into their types
+        let response = get_prompt(
+            &mut client,
+            "review",
+            json!({"code": "x + 1", "lines": "3"}),
+        )
+        .await;
+        assert_eq!(
+            response["result"],
+            json!({
+                "description": "Review a piece of code",
+                "messages": [
+                    {"role": "user", "content": {
+                        "type": "text", "text": "Review `x + 1` in at most 3 lines."
+                    }},
+                    {"role": "assistant", "content": {"type": "text", "text": "Sure."}}
+                ]
+            })
+        );
+
+        for arguments in [json!({}), json!({"code": "x", "lines": "many"})] {
+            let response = get_prompt(&mut client, "review", arguments).await;
+            assert_eq!(response["error"]["code"], error_codes::INVALID_PARAMS);
+        }
+        let response = get_prompt(&mut client, "unknown", json!({})).await;
+        assert_eq!(response["error"]["code"], error_codes::INVALID_PARAMS);
+    }
+}
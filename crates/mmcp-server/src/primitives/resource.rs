@@ -1 +1,658 @@
-pub trait Resource {}
+pub use mmcp_protocol::uri_template;
+
+use std::{borrow::Cow, collections::BTreeMap, future::Future, pin::Pin};
+
+use futures::FutureExt as _;
+use serde::Serialize;
+
+use mmcp_protocol::{
+    mcp::{
+        Annotations, BlobResourceContents, ReadResourceRequest, ReadResourceResult,
+        ReadResourceResultContents, ResourceUpdatedNotificationParams, TextResourceContents,
+    },
+    port::RPCSink as _,
+};
+
+use crate::{
+    error::McpError,
+    primitives::{
+        completion::Completer,
+        string_map::from_string_map,
+        tool::{Json, Text},
+    },
+    session::Sessions,
+};
+
+pub type BoxedResource = Box<dyn Resource + Send + Sync + 'static>;
+
+pub type BoxedResourceTemplate = Box<dyn ResourceTemplate + Send + Sync + 'static>;
+
+pub trait Resource {
+    /// The URI of the resource
+    fn uri(&self) -> Cow<'static, str>;
+
+    /// The human-readable name of the resource
+    fn name(&self) -> Cow<'static, str>;
+
+    /// The description of the resource
+    fn description(&self) -> Option<Cow<'static, str>>;
+
+    /// The MIME type of the resource, if known
+    fn mime_type(&self) -> Option<Cow<'static, str>>;
+
+    /// The annotations of the resource
+    fn annotations(&self) -> Option<Annotations>;
+
+    /// Read the contents of the resource
+    fn read(
+        &self,
+        request: ReadResourceRequest,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<ReadResourceResult>> + Send + '_>>;
+}
+
+pub trait TypedResource {
+    type Output;
+
+    /// The URI of the resource
+    fn uri(&self) -> Cow<'static, str>;
+
+    /// The human-readable name of the resource
+    fn name(&self) -> Cow<'static, str>;
+
+    /// The description of the resource
+    fn description(&self) -> Option<Cow<'static, str>>;
+
+    /// The MIME type of the resource, if known
+    fn mime_type(&self) -> Option<Cow<'static, str>>;
+
+    /// The annotations of the resource
+    fn annotations(&self) -> Option<Annotations>;
+
+    /// Read the contents of the resource
+    fn read(&self) -> impl Future<Output = Self::Output> + Send;
+}
+
+impl<T, O> Resource for T
+where
+    T: TypedResource<Output = O> + Sync,
+    O: IntoResourceContents,
+{
+    fn uri(&self) -> Cow<'static, str> {
+        self.uri()
+    }
+
+    fn name(&self) -> Cow<'static, str> {
+        self.name()
+    }
+
+    fn description(&self) -> Option<Cow<'static, str>> {
+        self.description()
+    }
+
+    fn mime_type(&self) -> Option<Cow<'static, str>> {
+        self.mime_type()
+    }
+
+    fn annotations(&self) -> Option<Annotations> {
+        self.annotations()
+    }
+
+    fn read(
+        &self,
+        request: ReadResourceRequest,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<ReadResourceResult>> + Send + '_>> {
+        let mime_type = self.mime_type();
+        Box::pin(self.read().map(move |output| {
+            Ok(ReadResourceResult {
+                contents: output
+                    .into_resource_contents(&request.params.uri, mime_type.as_deref())?,
+                meta: None,
+                extra: Default::default(),
+            })
+        }))
+    }
+}
+
+pub trait ResourceTemplate {
+    /// The RFC 6570 URI template of the resource, e.g. `db://{table}/{id}`
+    fn uri_template(&self) -> Cow<'static, str>;
+
+    /// The human-readable name of the resource template
+    fn name(&self) -> Cow<'static, str>;
+
+    /// The description of the resource template
+    fn description(&self) -> Option<Cow<'static, str>>;
+
+    /// The MIME type of the resources matching this template, if they all share the same type
+    fn mime_type(&self) -> Option<Cow<'static, str>>;
+
+    /// The annotations of the resource template
+    fn annotations(&self) -> Option<Annotations>;
+
+    /// The completer suggesting values for a URI template variable, if any
+    fn completer(&self, variable: &str) -> Option<&(dyn Completer + Send + Sync)> {
+        let _ = variable;
+        None
+    }
+
+    /// Read the contents of a resource whose URI matched the template
+    fn read(
+        &self,
+        request: ReadResourceRequest,
+        variables: BTreeMap<String, String>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<ReadResourceResult>> + Send + '_>>;
+}
+
+pub trait TypedResourceTemplate {
+    type Variables;
+    type Output;
+
+    /// The RFC 6570 URI template of the resource, e.g. `db://{table}/{id}`
+    fn uri_template(&self) -> Cow<'static, str>;
+
+    /// The human-readable name of the resource template
+    fn name(&self) -> Cow<'static, str>;
+
+    /// The description of the resource template
+    fn description(&self) -> Option<Cow<'static, str>>;
+
+    /// The MIME type of the resources matching this template, if they all share the same type
+    fn mime_type(&self) -> Option<Cow<'static, str>>;
+
+    /// The annotations of the resource template
+    fn annotations(&self) -> Option<Annotations>;
+
+    /// The completer suggesting values for a URI template variable, if any
+    fn completer(&self, variable: &str) -> Option<&(dyn Completer + Send + Sync)> {
+        let _ = variable;
+        None
+    }
+
+    /// Read the contents of a resource whose URI matched the template
+    fn read(&self, variables: Self::Variables) -> impl Future<Output = Self::Output> + Send;
+}
+
+impl<T, V, O> ResourceTemplate for T
+where
+    T: TypedResourceTemplate<Variables = V, Output = O> + Sync,
+    V: serde::de::DeserializeOwned,
+    O: IntoResourceContents,
+{
+    fn uri_template(&self) -> Cow<'static, str> {
+        self.uri_template()
+    }
+
+    fn name(&self) -> Cow<'static, str> {
+        self.name()
+    }
+
+    fn description(&self) -> Option<Cow<'static, str>> {
+        self.description()
+    }
+
+    fn mime_type(&self) -> Option<Cow<'static, str>> {
+        self.mime_type()
+    }
+
+    fn annotations(&self) -> Option<Annotations> {
+        self.annotations()
+    }
+
+    fn completer(&self, variable: &str) -> Option<&(dyn Completer + Send + Sync)> {
+        self.completer(variable)
+    }
+
+    fn read(
+        &self,
+        request: ReadResourceRequest,
+        variables: BTreeMap<String, String>,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<ReadResourceResult>> + Send + '_>> {
+        let variables = match from_string_map(variables) {
+            Ok(variables) => variables,
+            Err(e) => {
+                let error = McpError::invalid_params(format!("Invalid URI variables: {e}"));
+                return Box::pin(async move { Err(error.into()) });
+            }
+        };
+        let mime_type = self.mime_type();
+        Box::pin(self.read(variables).map(move |output| {
+            Ok(ReadResourceResult {
+                contents: output
+                    .into_resource_contents(&request.params.uri, mime_type.as_deref())?,
+                meta: None,
+                extra: Default::default(),
+            })
+        }))
+    }
+}
+
+/// Sends `notifications/resources/updated` to the clients subscribed to a resource.
+///
+/// Obtained from [`MCPServer::resource_notifier`](crate::MCPServer::resource_notifier).
+#[derive(Clone)]
+pub struct ResourceNotifier {
+    sessions: Sessions,
+}
+
+impl ResourceNotifier {
+    pub(crate) fn new(sessions: Sessions) -> Self {
+        Self { sessions }
+    }
+
+    /// Notify every session subscribed to `uri` that the resource has been updated.
+    ///
+    /// Sessions whose connection has already closed are skipped.
+    pub async fn updated(&self, uri: &str) {
+        for session in self.sessions.list() {
+            if !session.is_subscribed(uri) {
+                continue;
+            }
+            let _ = session
+                .peer()
+                .clone()
+                .send_notification(
+                    "notifications/resources/updated",
+                    ResourceUpdatedNotificationParams {
+                        uri: uri.to_string(),
+                        extra: Default::default(),
+                    },
+                )
+                .await;
+        }
+    }
+}
+
+/// Converts a value into the contents of a `resources/read` response.
+///
+/// `uri` and `mime_type` are the ones of the resource being read, and should be used for every
+/// returned item unless the implementation knows better.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` must implement `IntoResourceContents`",
+    note = "Wrap your type in `Text<{Self}>`, `Json<{Self}>` or `Blob<{Self}>` if it implements `Display`, `Serialize` or `Into<Vec<u8>>`."
+)]
+pub trait IntoResourceContents {
+    fn into_resource_contents(
+        self,
+        uri: &str,
+        mime_type: Option<&str>,
+    ) -> anyhow::Result<Vec<ReadResourceResultContents>>;
+}
+
+impl<T, E> IntoResourceContents for Result<T, E>
+where
+    T: IntoResourceContents,
+    E: std::fmt::Display,
+{
+    fn into_resource_contents(
+        self,
+        uri: &str,
+        mime_type: Option<&str>,
+    ) -> anyhow::Result<Vec<ReadResourceResultContents>> {
+        match self {
+            Ok(output) => output.into_resource_contents(uri, mime_type),
+            Err(error) => Err(anyhow::anyhow!("{}", error)),
+        }
+    }
+}
+
+impl<T> IntoResourceContents for Vec<T>
+where
+    T: IntoResourceContents,
+{
+    fn into_resource_contents(
+        self,
+        uri: &str,
+        mime_type: Option<&str>,
+    ) -> anyhow::Result<Vec<ReadResourceResultContents>> {
+        let mut contents = Vec::new();
+        for item in self {
+            contents.extend(item.into_resource_contents(uri, mime_type)?);
+        }
+        Ok(contents)
+    }
+}
+
+impl IntoResourceContents for String {
+    fn into_resource_contents(
+        self,
+        uri: &str,
+        mime_type: Option<&str>,
+    ) -> anyhow::Result<Vec<ReadResourceResultContents>> {
+        Ok(vec![ReadResourceResultContents::TextResourceContents(
+            TextResourceContents {
+                text: self,
+                uri: uri.to_string(),
+                mime_type: mime_type.map(ToString::to_string),
+                extra: Default::default(),
+            },
+        )])
+    }
+}
+
+impl IntoResourceContents for &str {
+    fn into_resource_contents(
+        self,
+        uri: &str,
+        mime_type: Option<&str>,
+    ) -> anyhow::Result<Vec<ReadResourceResultContents>> {
+        self.to_string().into_resource_contents(uri, mime_type)
+    }
+}
+
+impl<T> IntoResourceContents for Text<T>
+where
+    T: std::fmt::Display,
+{
+    fn into_resource_contents(
+        self,
+        uri: &str,
+        mime_type: Option<&str>,
+    ) -> anyhow::Result<Vec<ReadResourceResultContents>> {
+        self.0.to_string().into_resource_contents(uri, mime_type)
+    }
+}
+
+impl<T> IntoResourceContents for Json<T>
+where
+    T: Serialize,
+{
+    fn into_resource_contents(
+        self,
+        uri: &str,
+        mime_type: Option<&str>,
+    ) -> anyhow::Result<Vec<ReadResourceResultContents>> {
+        serde_json::to_string(&self.0)?
+            .into_resource_contents(uri, Some(mime_type.unwrap_or("application/json")))
+    }
+}
+
+/// Binary contents of a resource, sent as base64-encoded blob.
+pub struct Blob<T>(pub T);
+
+impl<T> IntoResourceContents for Blob<T>
+where
+    T: Into<Vec<u8>>,
+{
+    fn into_resource_contents(
+        self,
+        uri: &str,
+        mime_type: Option<&str>,
+    ) -> anyhow::Result<Vec<ReadResourceResultContents>> {
+        Ok(vec![ReadResourceResultContents::BlobResourceContents(
+            BlobResourceContents {
+                blob: self.0.into(),
+                uri: uri.to_string(),
+                mime_type: mime_type.map(ToString::to_string),
+                extra: Default::default(),
+            },
+        )])
+    }
+}
+
+impl IntoResourceContents for TextResourceContents {
+    fn into_resource_contents(
+        self,
+        _uri: &str,
+        _mime_type: Option<&str>,
+    ) -> anyhow::Result<Vec<ReadResourceResultContents>> {
+        Ok(vec![ReadResourceResultContents::TextResourceContents(self)])
+    }
+}
+
+impl IntoResourceContents for BlobResourceContents {
+    fn into_resource_contents(
+        self,
+        _uri: &str,
+        _mime_type: Option<&str>,
+    ) -> anyhow::Result<Vec<ReadResourceResultContents>> {
+        Ok(vec![ReadResourceResultContents::BlobResourceContents(self)])
+    }
+}
+
+impl IntoResourceContents for ReadResourceResultContents {
+    fn into_resource_contents(
+        self,
+        _uri: &str,
+        _mime_type: Option<&str>,
+    ) -> anyhow::Result<Vec<ReadResourceResultContents>> {
+        Ok(vec![self])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use mmcp_protocol::consts::error_codes;
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{MCPServer, test_utils::TestClient};
+
+    /// The configuration of the app
+    struct Config;
+
+    impl TypedResource for Config {
+        type Output = Json<serde_json::Value>;
+
+        fn uri(&self) -> Cow<'static, str> {
+            "config://app".into()
+        }
+
+        fn name(&self) -> Cow<'static, str> {
+            "config".into()
+        }
+
+        fn description(&self) -> Option<Cow<'static, str>> {
+            Some("The configuration of the app".into())
+        }
+
+        fn mime_type(&self) -> Option<Cow<'static, str>> {
+            None
+        }
+
+        fn annotations(&self) -> Option<Annotations> {
+            None
+        }
+
+        async fn read(&self) -> Self::Output {
+            Json(json!({"debug": true}))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_resource() {
+        let mut client =
+            TestClient::connect(MCPServer::new("test", "0.1.0").add_resource(Config)).await;
+
+        let response = client
+            .request(json!({"jsonrpc": "2.0", "id": 1, "method": "resources/list"}))
+            .await;
+        assert_eq!(
+            response["result"]["resources"],
+            json!([{
+                "uri": "config://app",
+                "name": "config",
+                "description": "The configuration of the app"
+            }])
+        );
+
+        let response = client
+            .request(
+                json!({"jsonrpc": "2.0", "id": 2, "method": "resources/read", "params": {
+                    "uri": "config://app"
+                }}),
+            )
+            .await;
+        assert_eq!(
+            response["result"]["contents"],
+            json!([{
+                "uri": "config://app",
+                "mimeType": "application/json",
+                "text": r#"{"debug":true}"#
+            }])
+        );
+
+        let response = client
+            .request(
+                json!({"jsonrpc": "2.0", "id": 3, "method": "resources/read", "params": {
+                    "uri": "config://other"
+                }}),
+            )
+            .await;
+        assert_eq!(response["error"]["code"], error_codes::RESOURCE_NOT_FOUND);
+        assert_eq!(response["error"]["data"], json!({"uri": "config://other"}));
+    }
+
+    #[derive(serde::Deserialize)]
+    struct RowVariables {
+        table: String,
+        id: u64,
+    }
+
+    /// A row of a table
+    struct Row;
+
+    impl TypedResourceTemplate for Row {
+        type Variables = RowVariables;
+        type Output = Json<serde_json::Value>;
+
+        fn uri_template(&self) -> Cow<'static, str> {
+            "db://{table}/{id}".into()
+        }
+
+        fn name(&self) -> Cow<'static, str> {
+            "row".into()
+        }
+
+        fn description(&self) -> Option<Cow<'static, str>> {
+            Some("A row of a table".into())
+        }
+
+        fn mime_type(&self) -> Option<Cow<'static, str>> {
+            None
+        }
+
+        fn annotations(&self) -> Option<Annotations> {
+            None
+        }
+
+        async fn read(&self, variables: Self::Variables) -> Self::Output {
+            Json(json!({"table": variables.table, "id": variables.id}))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_resource_template() {
+        let mut client =
+            TestClient::connect(MCPServer::new("test", "0.1.0").add_resource_template(Row)).await;
+
+        let response = client
+            .request(json!({"jsonrpc": "2.0", "id": 1, "method": "resources/templates/list"}))
+            .await;
+        assert_eq!(
+            response["result"]["resourceTemplates"],
+            json!([{
+                "uriTemplate": "db://{table}/{id}",
+                "name": "row",
+                "description": "A row of a table"
+            }])
+        );
+
+        // The variables are parsed from the URI into their types
+        let response = client
+            .request(
+                json!({"jsonrpc": "2.0", "id": 2, "method": "resources/read", "params": {
+                    "uri": "db://users/42"
+                }}),
+            )
+            .await;
+        let contents = &response["result"]["contents"][0];
+        assert_eq!(contents["uri"], "db://users/42");
+        assert_eq!(
+            serde_json::from_str::<Value>(contents["text"].as_str().unwrap()).unwrap(),
+            json!({"table": "users", "id": 42})
+        );
+
+        let response = client
+            .request(
+                json!({"jsonrpc": "2.0", "id": 3, "method": "resources/read", "params": {
+                    "uri": "db://users/first"
+                }}),
+            )
+            .await;
+        assert_eq!(response["error"]["code"], error_codes::INVALID_PARAMS);
+
+        let response = client
+            .request(
+                json!({"jsonrpc": "2.0", "id": 4, "method": "resources/read", "params": {
+                    "uri": "cache://users/42"
+                }}),
+            )
+            .await;
+        assert_eq!(response["error"]["code"], error_codes::RESOURCE_NOT_FOUND);
+    }
+
+    /// Ping, returning the notification sent before the response if there is one.
+    async fn notified_before_ping(client: &mut TestClient) -> Value {
+        client
+            .send(json!({"jsonrpc": "2.0", "id": 9, "method": "ping"}))
+            .await;
+        let message = client.next().await;
+        if message.get("method").is_some() {
+            client.response().await;
+            return message;
+        }
+        Value::Null
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions() {
+        let server = Arc::new(MCPServer::new("test", "0.1.0").add_resource(Config));
+        let notifier = server.resource_notifier();
+        let mut client = TestClient::serve(Arc::clone(&server));
+        let result = client.initialize("2025-03-26", json!({})).await;
+        assert_eq!(result["capabilities"]["resources"]["subscribe"], true);
+        let mut other = TestClient::connect(server).await;
+
+        let response = client
+            .request(
+                json!({"jsonrpc": "2.0", "id": 1, "method": "resources/subscribe", "params": {
+                    "uri": "config://app"
+                }}),
+            )
+            .await;
+        assert_eq!(response["result"], json!({}));
+        let response = client
+            .request(
+                json!({"jsonrpc": "2.0", "id": 2, "method": "resources/subscribe", "params": {
+                    "uri": "config://other"
+                }}),
+            )
+            .await;
+        assert_eq!(response["error"]["code"], error_codes::RESOURCE_NOT_FOUND);
+
+        // Only the subscribed session is notified, and only of the subscribed resource
+        notifier.updated("config://app").await;
+        assert_eq!(
+            notified_before_ping(&mut client).await,
+            json!({"jsonrpc": "2.0", "method": "notifications/resources/updated", "params": {
+                "uri": "config://app"
+            }})
+        );
+        assert_eq!(notified_before_ping(&mut other).await, Value::Null);
+        notifier.updated("config://other").await;
+        assert_eq!(notified_before_ping(&mut client).await, Value::Null);
+
+        let response = client
+            .request(
+                json!({"jsonrpc": "2.0", "id": 3, "method": "resources/unsubscribe", "params": {
+                    "uri": "config://app"
+                }}),
+            )
+            .await;
+        assert_eq!(response["result"], json!({}));
+        notifier.updated("config://app").await;
+        assert_eq!(notified_before_ping(&mut client).await, Value::Null);
+    }
+}
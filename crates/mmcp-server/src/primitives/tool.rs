@@ -1,4 +1,13 @@
-use std::{borrow::Cow, future::Future, pin::Pin};
+mod context;
+mod registry;
+mod state;
+
+pub use context::ToolContext;
+pub use registry::ToolRegistry;
+pub(crate) use state::States;
+pub use state::{MissingState, State};
+
+use std::{borrow::Cow, future::Future, pin::Pin, sync::Arc};
 
 use futures::FutureExt as _;
 use serde::Serialize;
@@ -10,6 +19,9 @@ use mmcp_protocol::mcp::{
 
 pub type BoxedTool = Box<dyn Tool + Send + Sync + 'static>;
 
+/// A tool shared between the [`ToolRegistry`] and the requests executing it.
+pub type SharedTool = Arc<dyn Tool + Send + Sync + 'static>;
+
 pub trait Tool {
     /// The name of the tool
     fn name(&self) -> Cow<'static, str>;
@@ -20,6 +32,12 @@ pub trait Tool {
     /// The parameters of the tool
     fn input_schema(&self) -> Cow<'static, str>;
 
+    /// The JSON schema of the `structuredContent` of the results, if the tool returns structured
+    /// results. Only sent to clients on protocol versions that support it.
+    fn output_schema(&self) -> Option<Cow<'static, str>> {
+        None
+    }
+
     /// The annotations of the tool
     fn annotations(&self) -> ToolAnnotations;
 
@@ -27,6 +45,7 @@ pub trait Tool {
     fn execute(
         &self,
         request: CallToolRequest,
+        context: ToolContext,
     ) -> Pin<Box<dyn Future<Output = CallToolResult> + Send + '_>>;
 }
 
@@ -43,11 +62,21 @@ pub trait TypedTool {
     /// The parameters of the tool
     fn input_schema(&self) -> Cow<'static, str>;
 
+    /// The JSON schema of the `structuredContent` of the results, if the tool returns structured
+    /// results. Only sent to clients on protocol versions that support it.
+    fn output_schema(&self) -> Option<Cow<'static, str>> {
+        None
+    }
+
     /// The annotations of the tool
     fn annotations(&self) -> ToolAnnotations;
 
     /// Execute the tool
-    fn execute(&self, arguments: Self::Input) -> impl Future<Output = Self::Output> + Send;
+    fn execute(
+        &self,
+        arguments: Self::Input,
+        context: ToolContext,
+    ) -> impl Future<Output = Self::Output> + Send;
 }
 
 impl<T, I, O> Tool for T
@@ -68,6 +97,10 @@ where
         self.input_schema()
     }
 
+    fn output_schema(&self) -> Option<Cow<'static, str>> {
+        self.output_schema()
+    }
+
     fn annotations(&self) -> ToolAnnotations {
         self.annotations()
     }
@@ -75,6 +108,7 @@ where
     fn execute(
         &self,
         request: CallToolRequest,
+        context: ToolContext,
     ) -> Pin<Box<dyn Future<Output = CallToolResult> + Send + '_>> {
         let input = match serde_json::from_value(serde_json::Value::Object(
             request.params.arguments.clone().unwrap_or_default(),
@@ -96,7 +130,10 @@ where
                 });
             }
         };
-        Box::pin(self.execute(input).map(|output| output.into_tool_result()))
+        Box::pin(
+            self.execute(input, context)
+                .map(|output| output.into_tool_result()),
+        )
     }
 }
 
@@ -177,6 +214,9 @@ impl IntoToolResult for &str {
     }
 }
 
+/// A result serialized as JSON. It is sent as text, and also as `structuredContent` if it
+/// serializes to an object, for clients on protocol versions that support it. `#[tool]` declares
+/// the schema of `T` as the `outputSchema` only if it describes an object.
 pub struct Json<T>(pub T);
 
 impl<T> IntoToolResult for Json<T>
@@ -184,16 +224,23 @@ where
     T: Serialize,
 {
     fn into_tool_result(self) -> CallToolResult {
+        let value = match serde_json::to_value(&self.0) {
+            Ok(value) => value,
+            Err(e) => {
+                return CallToolResult {
+                    is_error: Some(true),
+                    ..format!("Error: serializing output: {e}").into_tool_result()
+                };
+            }
+        };
+        let text = value.to_string();
+        let mut extra = serde_json::Map::new();
+        if value.is_object() {
+            extra.insert("structuredContent".into(), value);
+        }
         CallToolResult {
-            content: vec![CallToolResultContent::TextContent(TextContent {
-                text: serde_json::to_string(&self.0).unwrap(),
-                annotations: None,
-                r#type: Default::default(),
-                extra: Default::default(),
-            })],
-            is_error: None,
-            meta: None,
-            extra: Default::default(),
+            extra,
+            ..text.into_tool_result()
         }
     }
 }
@@ -263,3 +310,31 @@ where
         self.0.to_string().into_tool_result()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let result = Json(json!({"x": 1})).into_tool_result();
+        assert_eq!(result.extra["structuredContent"], json!({"x": 1}));
+        assert_eq!(result.is_error, None);
+
+        let result = Json(vec![1, 2]).into_tool_result();
+        assert!(!result.extra.contains_key("structuredContent"));
+        let CallToolResultContent::TextContent(content) = &result.content[0] else {
+            panic!("expected text content");
+        };
+        assert_eq!(content.text, "[1,2]");
+
+        // Maps with non-string keys cannot be serialized to JSON
+        let result = Json(HashMap::from([((1, 2), 3)])).into_tool_result();
+        assert_eq!(result.is_error, Some(true));
+        assert!(!result.extra.contains_key("structuredContent"));
+    }
+}
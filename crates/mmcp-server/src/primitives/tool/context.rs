@@ -0,0 +1,238 @@
+use std::{path::Path, sync::Arc};
+
+use mmcp_protocol::{
+    mcp::{
+        CallToolRequest, LoggingLevel, LoggingMessageNotificationParams,
+        ProgressNotificationParams, ProgressToken, RequestId, Root,
+    },
+    port::RPCSink as _,
+};
+use tokio_util::sync::CancellationToken;
+
+use super::States;
+use crate::{
+    roots,
+    sampling::Sampling,
+    session::{Peer, Session},
+};
+
+/// The context of a `tools/call` request, for reporting progress, logging to the client and
+/// noticing cancellation while the tool runs.
+#[derive(Clone)]
+pub struct ToolContext {
+    request_id: RequestId,
+    progress_token: Option<ProgressToken>,
+    session: Arc<Session>,
+    cancellation_token: CancellationToken,
+    states: Arc<States>,
+}
+
+impl ToolContext {
+    pub(crate) fn new(
+        request_id: RequestId,
+        request: &CallToolRequest,
+        session: Arc<Session>,
+        cancellation_token: CancellationToken,
+        states: Arc<States>,
+    ) -> Self {
+        let progress_token = request
+            .params
+            .extra
+            .get("_meta")
+            .and_then(|meta| meta.get("progressToken"))
+            .and_then(|token| serde_json::from_value(token.clone()).ok());
+        Self {
+            request_id,
+            progress_token,
+            session,
+            cancellation_token,
+            states,
+        }
+    }
+
+    /// The id of the `tools/call` request
+    pub fn request_id(&self) -> &RequestId {
+        &self.request_id
+    }
+
+    /// The progress token given by the client, if it wants progress notifications
+    pub fn progress_token(&self) -> Option<&ProgressToken> {
+        self.progress_token.as_ref()
+    }
+
+    /// The session the request was received on
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// The sink for sending messages to the client
+    pub fn peer(&self) -> &Peer {
+        self.session.peer()
+    }
+
+    /// Cancelled when the client cancels the request
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation_token
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation_token.is_cancelled()
+    }
+
+    /// The state of type `S` added with [`MCPServer::with_state`](crate::MCPServer::with_state)
+    pub fn state<S: Send + Sync + 'static>(&self) -> Option<&S> {
+        self.states.get()
+    }
+
+    /// The roots the client exposed to the server, or an empty list if it does not support roots
+    /// or they have not arrived yet.
+    pub fn roots(&self) -> Vec<Root> {
+        self.session.roots().unwrap_or_default()
+    }
+
+    /// Whether the path is inside one of the roots of the client. See [`roots::is_within_roots`].
+    pub fn is_within_roots(&self, path: impl AsRef<Path>) -> bool {
+        roots::is_within_roots(&self.roots(), path)
+    }
+
+    /// Ask the client for an LLM completion with `sampling/createMessage`.
+    pub fn sample(&self) -> Sampling {
+        Sampling::new(Arc::clone(&self.session))
+    }
+
+    /// Send `notifications/progress` to the client. Does nothing if the client did not ask for
+    /// progress with a progress token.
+    pub async fn report_progress(&self, progress: f64, total: Option<f64>) -> anyhow::Result<()> {
+        let Some(progress_token) = self.progress_token.clone() else {
+            return Ok(());
+        };
+        self.peer()
+            .clone()
+            .send_notification(
+                "notifications/progress",
+                ProgressNotificationParams {
+                    message: None,
+                    progress,
+                    progress_token,
+                    total,
+                    extra: Default::default(),
+                },
+            )
+            .await
+    }
+
+    /// Send `notifications/message` to the client, if the level is at or above the one it set
+    /// with `logging/setLevel`. It is queued with the messages of the
+    /// [`LoggingLayer`](crate::logging::LoggingLayer), so they arrive in order, and returns once
+    /// it is sent.
+    pub async fn log(&self, level: LoggingLevel, message: impl Into<String>) -> anyhow::Result<()> {
+        if !self.session.is_log_enabled(&level) {
+            return Ok(());
+        }
+        self.session
+            .send_log(LoggingMessageNotificationParams {
+                data: message.into().into(),
+                level,
+                logger: None,
+                extra: Default::default(),
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mmcp_protocol::mcp::LoggingLevel;
+    use serde_json::json;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    use crate::{
+        MCPServer,
+        primitives::tool::IntoToolResult as _,
+        test_utils::{TestClient, TestTool, call_tool},
+    };
+
+    #[tokio::test]
+    async fn test_report_progress() {
+        let count = TestTool::new("count", |_, context| async move {
+            for n in 1..=3 {
+                context.report_progress(n as f64, Some(3.0)).await.unwrap();
+            }
+            serde_json::to_string(context.request_id())
+                .unwrap()
+                .into_tool_result()
+        });
+        let mut client = TestClient::connect(MCPServer::new("test", "0.1.0").add_tool(count)).await;
+
+        client
+            .send(
+                json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {
+                    "name": "count", "arguments": {}, "_meta": {"progressToken": "token"}
+                }}),
+            )
+            .await;
+        for n in 1..=3 {
+            assert_eq!(
+                client.next().await,
+                json!({"jsonrpc": "2.0", "method": "notifications/progress", "params": {
+                    "progressToken": "token", "progress": n as f64, "total": 3.0
+                }})
+            );
+        }
+        let response = client.next().await;
+        assert_eq!(response["result"]["content"][0]["text"], "1");
+
+        // Nothing is reported without a progress token
+        client
+            .send(
+                json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {
+                    "name": "count", "arguments": {}
+                }}),
+            )
+            .await;
+        let response = client.next().await;
+        assert_eq!(response["result"]["content"][0]["text"], "2");
+    }
+
+    #[tokio::test]
+    async fn test_log() {
+        let log = TestTool::new("log", |_, context| async move {
+            for n in 0..5 {
+                tracing::info!(target: "test", n, "layer");
+                context.log(LoggingLevel::Debug, "filtered").await.unwrap();
+                context
+                    .log(LoggingLevel::Info, format!("tool {n}"))
+                    .await
+                    .unwrap();
+            }
+            "done".into_tool_result()
+        });
+        let server = MCPServer::new("test", "0.1.0").add_tool(log);
+        let subscriber = tracing_subscriber::registry().with(server.logging_layer());
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let mut client = TestClient::connect(server).await;
+        client
+            .request(
+                json!({"jsonrpc": "2.0", "id": 1, "method": "logging/setLevel", "params": {
+                    "level": "info"
+                }}),
+            )
+            .await;
+
+        // The logs of the tool and of the layer keep their order, and arrive before the response
+        client.send(call_tool(2, "log")).await;
+        let mut messages = Vec::new();
+        loop {
+            let message = client.next().await;
+            if message.get("method").is_none() {
+                assert_eq!(message["result"]["content"][0]["text"], "done");
+                break;
+            }
+            assert_eq!(message["method"], "notifications/message");
+            let data = &message["params"]["data"];
+            messages.push(data.get("message").unwrap_or(data).clone());
+        }
+        let expected = (0..5).flat_map(|n| [json!("layer"), json!(format!("tool {n}"))]);
+        assert_eq!(messages, expected.collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+
+use mmcp_protocol::port::RPCSink as _;
+
+use super::{SharedTool, Tool};
+use crate::session::Sessions;
+
+/// The tools of a server. Cloning gives another handle to the same tools, so tools can be added
+/// and removed while the server is running.
+///
+/// Every change made through [`add`](Self::add) and [`remove`](Self::remove) sends
+/// `notifications/tools/list_changed` to the connected sessions.
+#[derive(Clone)]
+pub struct ToolRegistry {
+    tools: Arc<RwLock<BTreeMap<Cow<'static, str>, SharedTool>>>,
+    sessions: Sessions,
+}
+
+impl ToolRegistry {
+    pub(crate) fn new(sessions: Sessions) -> Self {
+        Self {
+            tools: Default::default(),
+            sessions,
+        }
+    }
+
+    /// Insert a tool without notifying the sessions, for building the server.
+    pub(crate) fn insert(&self, tool: SharedTool) -> Option<SharedTool> {
+        self.tools.write().unwrap().insert(tool.name(), tool)
+    }
+
+    /// Add a tool, replacing the one with the same name. Returns the replaced tool.
+    pub async fn add(&self, tool: impl Tool + Send + Sync + 'static) -> Option<SharedTool> {
+        let replaced = self.insert(Arc::new(tool));
+        self.notify_list_changed().await;
+        replaced
+    }
+
+    /// Remove the tool with the name. Nothing is sent if there was no such tool.
+    pub async fn remove(&self, name: &str) -> Option<SharedTool> {
+        let removed = self.tools.write().unwrap().remove(name);
+        if removed.is_some() {
+            self.notify_list_changed().await;
+        }
+        removed
+    }
+
+    pub fn get(&self, name: &str) -> Option<SharedTool> {
+        self.tools.read().unwrap().get(name).cloned()
+    }
+
+    /// A snapshot of the tools, ordered by name.
+    pub fn list(&self) -> Vec<SharedTool> {
+        self.tools.read().unwrap().values().cloned().collect()
+    }
+
+    /// Send `notifications/tools/list_changed` to every initialized session that was told
+    /// `tools.listChanged`. Sessions that initialized before the registry was taken were not, and
+    /// have to list the tools again themselves.
+    ///
+    /// Sessions whose connection has already closed are skipped.
+    pub async fn notify_list_changed(&self) {
+        for session in self.sessions.list() {
+            let list_changed = session
+                .server_capabilities()
+                .and_then(|capabilities| capabilities.tools.as_ref())
+                .is_some_and(|tools| tools.list_changed == Some(true));
+            if !session.is_initialized() || !list_changed {
+                continue;
+            }
+            let _ = session
+                .peer()
+                .clone()
+                .send_notification("notifications/tools/list_changed", serde_json::Value::Null)
+                .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        MCPServer,
+        test_utils::{TestClient, noop},
+    };
+
+    /// Ping, returning the method of the notification sent before the response if there is one.
+    async fn notified_before_ping(client: &mut TestClient) -> Value {
+        client
+            .send(json!({"jsonrpc": "2.0", "id": 2, "method": "ping"}))
+            .await;
+        let message = client.next().await;
+        if message.get("method").is_some() {
+            client.response().await;
+        }
+        message["method"].clone()
+    }
+
+    #[tokio::test]
+    async fn test_tool_registry() {
+        let server = Arc::new(MCPServer::new("test", "0.1.0").add_tool(noop("a")));
+        // Initialized before the registry is taken, so never told that the list may change
+        let mut early = TestClient::serve(Arc::clone(&server));
+        let result = early.initialize("2025-03-26", json!({})).await;
+        assert_eq!(result["capabilities"], json!({"tools": {}}));
+
+        let registry = server.tool_registry();
+        let mut late = TestClient::serve(Arc::clone(&server));
+        let response = late
+            .request(
+                json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+                    "capabilities": {},
+                    "clientInfo": {"name": "test-client", "version": "0.1.0"},
+                    "protocolVersion": "2025-03-26"
+                }}),
+            )
+            .await;
+        assert_eq!(
+            response["result"]["capabilities"],
+            json!({"tools": {"listChanged": true}})
+        );
+
+        // Not sent before `notifications/initialized`
+        assert!(registry.add(noop("b")).await.is_none());
+        late.send(json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .await;
+        assert_eq!(notified_before_ping(&mut late).await, Value::Null);
+
+        assert!(registry.remove("a").await.is_some());
+        assert_eq!(
+            notified_before_ping(&mut late).await,
+            "notifications/tools/list_changed"
+        );
+        assert_eq!(notified_before_ping(&mut early).await, Value::Null);
+
+        assert!(registry.remove("a").await.is_none());
+        assert_eq!(notified_before_ping(&mut late).await, Value::Null);
+        let names = registry
+            .list()
+            .iter()
+            .map(|tool| tool.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["b"]);
+    }
+}
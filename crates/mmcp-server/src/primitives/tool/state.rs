@@ -0,0 +1,114 @@
+use std::{
+    any::{Any, TypeId, type_name},
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use mmcp_protocol::mcp::CallToolResult;
+
+use super::{IntoToolResult, ToolContext};
+
+/// The values added with [`MCPServer::with_state`](crate::MCPServer::with_state), one per type.
+#[derive(Clone, Default)]
+pub(crate) struct States(HashMap<TypeId, Arc<dyn Any + Send + Sync>>);
+
+impl States {
+    pub(crate) fn insert<S: Send + Sync + 'static>(&mut self, state: S) {
+        self.0.insert(TypeId::of::<S>(), Arc::new(state));
+    }
+
+    pub(crate) fn get<S: Send + Sync + 'static>(&self) -> Option<&S> {
+        self.0.get(&TypeId::of::<S>())?.downcast_ref()
+    }
+}
+
+/// A parameter of a `#[tool]` fn receiving the state of type `S` added with
+/// [`MCPServer::with_state`](crate::MCPServer::with_state). It is cloned for each call, so wrap
+/// expensive state in an `Arc`; pools and clients are usually cheap to clone already.
+///
+/// ```ignore
+/// #[tool]
+/// async fn find_user(State(pool): State<PgPool>, id: i64) -> Json<User> {
+///     ...
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct State<S>(pub S);
+
+impl<S: Clone + Send + Sync + 'static> State<S> {
+    /// Extract the state from the context of the call, failing if no state of type `S` was added
+    /// to the server.
+    pub fn from_context(context: &ToolContext) -> Result<Self, MissingState> {
+        context
+            .state::<S>()
+            .map(|state| State(state.clone()))
+            .ok_or(MissingState(type_name::<S>()))
+    }
+}
+
+/// The error of a tool taking a state that was not added with
+/// [`MCPServer::with_state`](crate::MCPServer::with_state). `#[tool]` answers it as an error
+/// result without calling the fn.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("no state of type {0} was added with MCPServer::with_state")]
+pub struct MissingState(&'static str);
+
+impl IntoToolResult for MissingState {
+    fn into_tool_result(self) -> CallToolResult {
+        CallToolResult {
+            is_error: Some(true),
+            ..format!("Error: {self}").into_tool_result()
+        }
+    }
+}
+
+impl<S> Deref for State<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.0
+    }
+}
+
+impl<S> DerefMut for State<S> {
+    fn deref_mut(&mut self) -> &mut S {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        MCPServer,
+        test_utils::{TestClient, TestTool, call_tool},
+    };
+
+    #[derive(Clone)]
+    struct Greeting(&'static str);
+
+    /// Greets with the greeting in the state
+    fn greet() -> TestTool {
+        TestTool::new("greet", |_, context| {
+            let result = State::from_context(&context)
+                .map(|State(Greeting(greeting))| greeting.into_tool_result());
+            async move { result.unwrap_or_else(IntoToolResult::into_tool_result) }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_state() {
+        let server = MCPServer::new("test", "0.1.0")
+            .add_tool(greet())
+            .with_state(Greeting("Hi"));
+        let mut client = TestClient::connect(server).await;
+        let response = client.request(call_tool(1, "greet")).await;
+        assert_eq!(response["result"]["content"][0]["text"], "Hi");
+
+        let server = MCPServer::new("test", "0.1.0").add_tool(greet());
+        let mut client = TestClient::connect(server).await;
+        let response = client.request(call_tool(1, "greet")).await;
+        assert_eq!(response["result"]["isError"], true);
+    }
+}
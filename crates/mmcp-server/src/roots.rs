@@ -0,0 +1,96 @@
+use std::path::{Component, Path, PathBuf};
+
+use mmcp_protocol::{mcp::Root, uri_template::percent_decode};
+
+/// Whether the path is one of the roots or inside of one.
+///
+/// Paths are compared lexically after resolving `.` and `..`, so symlinks are not followed and
+/// relative paths never match. Canonicalize the path first if it may contain symlinks. Roots that
+/// are not `file://` URIs are ignored.
+pub fn is_within_roots(roots: &[Root], path: impl AsRef<Path>) -> bool {
+    let Some(path) = normalize(path.as_ref()) else {
+        return false;
+    };
+    roots
+        .iter()
+        .filter_map(|root| root_path(&root.uri))
+        .any(|root| path.starts_with(root))
+}
+
+/// The local path of a `file://` URI.
+pub fn root_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    // Only local files are supported, with an empty or `localhost` authority
+    let path = rest.strip_prefix("localhost").unwrap_or(rest);
+    if !path.starts_with('/') {
+        return None;
+    }
+    let path = percent_decode(path)?;
+    // `file:///C:/dir` is `C:/dir` on Windows
+    let path = if cfg!(windows) {
+        path.trim_start_matches('/').to_string()
+    } else {
+        path
+    };
+    normalize(Path::new(&path))
+}
+
+/// Resolve `.` and `..` without touching the file system.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    if !path.is_absolute() {
+        return None;
+    }
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    Some(normalized)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn roots(uris: &[&str]) -> Vec<Root> {
+        uris.iter()
+            .map(|uri| Root {
+                name: None,
+                uri: uri.to_string(),
+                extra: Default::default(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_is_within_roots() {
+        let roots = roots(&["file:///home/user/project", "file:///tmp/my%20dir/"]);
+        assert!(is_within_roots(&roots, "/home/user/project"));
+        assert!(is_within_roots(&roots, "/home/user/project/src/main.rs"));
+        assert!(is_within_roots(&roots, "/tmp/my dir/a.txt"));
+        assert!(is_within_roots(
+            &roots,
+            "/home/user/project/src/../Cargo.toml"
+        ));
+
+        assert!(!is_within_roots(&roots, "/home/user/project2"));
+        assert!(!is_within_roots(&roots, "/home/user/project/../secret"));
+        assert!(!is_within_roots(&roots, "project/src"));
+        assert!(!is_within_roots(&roots, "/tmp"));
+    }
+
+    #[test]
+    fn test_root_path() {
+        assert_eq!(
+            root_path("file://localhost/etc/hosts"),
+            Some(PathBuf::from("/etc/hosts"))
+        );
+        assert_eq!(root_path("https://example.com/dir"), None);
+        assert_eq!(root_path("file://server/share"), None);
+    }
+}
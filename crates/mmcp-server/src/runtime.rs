@@ -1,93 +1,276 @@
 pub mod notification_handlers;
 pub mod request_handlers;
 
-use crate::MCPServer;
+use crate::{
+    MCPServer,
+    error::McpError,
+    session::{Peer, Session},
+    shutdown::ShutdownSummary,
+};
+use futures::{FutureExt as _, select};
 use mmcp_protocol::{
     mcp::{
-        JSONRPCBatchResponse, JSONRPCMessage, JsonrpcBatchRequestItem, JsonrpcBatchResponseItem,
+        JSONRPCBatchResponse, JSONRPCError, JSONRPCMessage, JSONRPCNotification, JSONRPCRequest,
+        JsonrpcBatchRequestItem, JsonrpcBatchResponseItem, RequestId,
     },
     port::{RPCPort, RPCSink},
 };
-use std::sync::Arc;
+use std::{
+    panic::AssertUnwindSafe,
+    pin::pin,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
 use tokio::spawn;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 
 /// Runtime for handling messages concurrently after initialization
 #[derive(Clone)]
-pub struct MCPServerRuntime<S: RPCSink + Clone + Send + 'static> {
+pub struct MCPServerRuntime {
     server: Arc<MCPServer>,
-    sink: S,
+    session: Arc<Session>,
+    /// The tasks spawned by `dispatch`, waited for on shutdown
+    tasks: TaskTracker,
+    /// The number of requests answered, for counting those completed while draining
+    answered: Arc<AtomicUsize>,
+    /// How long in-flight requests are waited for once the session ends
+    drain_timeout: Duration,
 }
 
-impl<S: RPCSink + Clone + Send + 'static> MCPServerRuntime<S> {
-    /// Construct runtime from server and sink
-    pub fn new(server: MCPServer, sink: S) -> Self {
+impl MCPServerRuntime {
+    /// Construct runtime from server and the peer of the session
+    pub fn new(server: Arc<MCPServer>, peer: Peer) -> Self {
         MCPServerRuntime {
-            server: Arc::new(server),
-            sink,
+            drain_timeout: server.shutdown_timeout,
+            server,
+            session: Arc::new(Session::new(peer)),
+            tasks: TaskTracker::new(),
+            answered: Default::default(),
         }
     }
 
-    /// Run the server: initialization + message loop
-    pub async fn run<P: RPCPort>(self, mut port: P) -> anyhow::Result<()> {
+    /// Set how long in-flight requests are waited for once the session ends, which is the
+    /// shutdown timeout of the server otherwise. With zero, they are cancelled right away.
+    pub fn with_drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Run the server: initialization + message loop, until the connection is closed or the
+    /// `shutdown` future completes. Then drain the in-flight requests.
+    pub async fn run<P: RPCPort>(
+        self,
+        port: P,
+        shutdown: impl Future<Output = ()>,
+    ) -> anyhow::Result<ShutdownSummary> {
+        self.server.sessions.insert(Arc::clone(&self.session));
+        let result = self.run_session(port, shutdown).await;
+        self.server.sessions.remove(self.session.id());
+        result
+    }
+
+    async fn run_session<P: RPCPort>(
+        &self,
+        mut port: P,
+        shutdown: impl Future<Output = ()>,
+    ) -> anyhow::Result<ShutdownSummary> {
+        let mut shutdown = pin!(shutdown.fuse());
         // Initialization phase
-        let mut sink = self.sink.clone();
-        let queued = self.server.initialize(&mut port, &mut sink).await?;
+        let queued = select! {
+            queued = self.server.initialize(&mut port, &self.session).fuse() => queued?,
+            () = shutdown => return Ok(ShutdownSummary::default()),
+        };
+        // Fetched in the background, since the response arrives through the loop below
+        if self.session.supports_roots() {
+            let session = Arc::clone(&self.session);
+            spawn(async move {
+                if let Err(e) = session.refresh_roots().await {
+                    tracing::warn!("Failed to fetch roots: {e}");
+                }
+            });
+        }
         // Handle any queued messages
         for msg in queued {
             self.dispatch(msg).await?;
         }
         // Main loop: process messages as they arrive
-        while let Ok(Some(msg)) = port.progress().await {
-            self.dispatch(msg).await?;
+        let shutting_down = loop {
+            select! {
+                msg = port.progress().fuse() => match msg {
+                    Ok(Some(msg)) => self.dispatch(msg).await?,
+                    _ => break false,
+                },
+                () = shutdown => break true,
+            }
+        };
+        let summary = self.drain(&mut port, shutting_down).await;
+        port.close().await?;
+        Ok(summary)
+    }
+
+    /// Wait for the in-flight requests until the drain timeout, then cancel the rest and answer
+    /// them with an error.
+    ///
+    /// When shutting down with the connection still open, the port keeps being polled so that
+    /// responses to requests sent by tools and cancellations still arrive, but new requests are
+    /// rejected.
+    async fn drain<P: RPCPort>(&self, port: &mut P, shutting_down: bool) -> ShutdownSummary {
+        let mut summary = ShutdownSummary::default();
+        let answered = self.answered.load(Ordering::Relaxed);
+        self.tasks.close();
+
+        let mut deadline = pin!(tokio::time::sleep(self.drain_timeout).fuse());
+        let mut finished = pin!(self.tasks.wait().fuse());
+        let mut connected = shutting_down;
+        let timed_out = loop {
+            select! {
+                () = finished => break false,
+                () = deadline => break true,
+                msg = progress_while(connected, port).fuse() => match msg {
+                    Ok(Some(msg)) => summary.rejected += self.reject(msg).await,
+                    _ => connected = false,
+                },
+            }
+        };
+
+        if timed_out {
+            let mut sink = self.session.peer().clone();
+            for request_id in self.session.cancel_all_requests() {
+                summary.abandoned += 1;
+                let error = McpError::internal("Server shut down before the request finished");
+                let _ = sink
+                    .send_message(JSONRPCMessage::JSONRPCError(
+                        error.into_response(request_id),
+                    ))
+                    .await;
+            }
+        }
+        summary.completed = self.answered.load(Ordering::Relaxed) - answered;
+        summary
+    }
+
+    /// Answer the requests received while draining with an error, and handle anything else in
+    /// the background, since handlers like the roots refresh wait for responses from the port.
+    /// Returns the number of rejected requests.
+    async fn reject(&self, message: JSONRPCMessage) -> usize {
+        let error = || McpError::internal("Server is shutting down");
+        let mut sink = self.session.peer().clone();
+        match message {
+            JSONRPCMessage::JSONRPCRequest(request) => {
+                let _ = sink
+                    .send_message(JSONRPCMessage::JSONRPCError(
+                        error().into_response(request.id),
+                    ))
+                    .await;
+                1
+            }
+            JSONRPCMessage::JSONRPCBatchRequest(batch) => {
+                let responses = batch
+                    .0
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        JsonrpcBatchRequestItem::JSONRPCRequest(request) => {
+                            Some(JsonrpcBatchResponseItem::JSONRPCError(
+                                error().into_response(request.id),
+                            ))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                let rejected = responses.len();
+                let _ = sink
+                    .send_message(JSONRPCMessage::JSONRPCBatchResponse(JSONRPCBatchResponse(
+                        responses,
+                    )))
+                    .await;
+                rejected
+            }
+            JSONRPCMessage::JSONRPCNotification(notification) => {
+                self.spawn_notification(notification);
+                0
+            }
+            _ => 0,
         }
-        Ok(())
     }
 
     async fn dispatch(&self, message: JSONRPCMessage) -> anyhow::Result<()> {
         match message {
             JSONRPCMessage::JSONRPCRequest(request) => {
                 let server = Arc::clone(&self.server);
-                let mut sink = self.sink.clone();
-                spawn(async move {
-                    let msg = match server.handle_request(request).await {
-                        Ok(JsonrpcBatchResponseItem::JSONRPCResponse(resp)) => {
+                let session = Arc::clone(&self.session);
+                let Some(token) = session.start_request(request.id.clone()) else {
+                    let error = duplicate_request_error(request.id);
+                    let _ = session
+                        .peer()
+                        .clone()
+                        .send_message(JSONRPCMessage::JSONRPCError(error))
+                        .await;
+                    return Ok(());
+                };
+                let answered = Arc::clone(&self.answered);
+                self.tasks.spawn(async move {
+                    let response = handle_cancellable(&server, &session, request, token).await;
+                    let msg = match count_answered(&answered, response) {
+                        // Cancelled requests must not be responded to
+                        None => return,
+                        Some(JsonrpcBatchResponseItem::JSONRPCResponse(resp)) => {
                             JSONRPCMessage::JSONRPCResponse(resp)
                         }
-                        Ok(JsonrpcBatchResponseItem::JSONRPCError(err)) => {
+                        Some(JsonrpcBatchResponseItem::JSONRPCError(err)) => {
                             JSONRPCMessage::JSONRPCError(err)
                         }
-                        Err(e) => {
-                            eprintln!("Request handler error: {e}");
-                            return;
-                        }
                     };
-                    let _ = sink.send_message(msg).await;
+                    let _ = session.peer().clone().send_message(msg).await;
                 });
             }
             JSONRPCMessage::JSONRPCNotification(notification) => {
-                let server = Arc::clone(&self.server);
-                spawn(async move {
-                    let _ = server.handle_notification(notification).await;
-                });
+                self.spawn_notification(notification);
             }
             JSONRPCMessage::JSONRPCBatchRequest(batch) => {
                 let server = Arc::clone(&self.server);
-                let mut sink = self.sink.clone();
-                spawn(async move {
-                    let mut handles = Vec::new();
-                    for item in batch.0 {
-                        if let JsonrpcBatchRequestItem::JSONRPCRequest(req) = item {
-                            let server = Arc::clone(&server);
-                            handles.push(spawn(async move { server.handle_request(req).await }));
+                let session = Arc::clone(&self.session);
+                let requests = batch
+                    .0
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        JsonrpcBatchRequestItem::JSONRPCRequest(req) => {
+                            match session.start_request(req.id.clone()) {
+                                Some(token) => Some(Ok((req, token))),
+                                None => Some(Err(req.id)),
+                            }
                         }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                let answered = Arc::clone(&self.answered);
+                self.tasks.spawn(async move {
+                    let mut handles = Vec::new();
+                    for request in requests {
+                        let server = Arc::clone(&server);
+                        let session = Arc::clone(&session);
+                        handles.push(spawn(async move {
+                            match request {
+                                Ok((req, token)) => {
+                                    handle_cancellable(&server, &session, req, token).await
+                                }
+                                Err(request_id) => Some(JsonrpcBatchResponseItem::JSONRPCError(
+                                    duplicate_request_error(request_id),
+                                )),
+                            }
+                        }));
                     }
                     let mut responses = Vec::new();
                     for handle in handles {
-                        if let Ok(Ok(resp)) = handle.await {
+                        if let Some(resp) = count_answered(&answered, handle.await.ok().flatten()) {
                             responses.push(resp);
                         }
                     }
-                    let _ = sink
+                    let _ = session
+                        .peer()
+                        .clone()
                         .send_message(JSONRPCMessage::JSONRPCBatchResponse(JSONRPCBatchResponse(
                             responses,
                         )))
@@ -98,4 +281,72 @@ impl<S: RPCSink + Clone + Send + 'static> MCPServerRuntime<S> {
         }
         Ok(())
     }
+
+    fn spawn_notification(&self, notification: JSONRPCNotification) {
+        let server = Arc::clone(&self.server);
+        let session = Arc::clone(&self.session);
+        self.tasks.spawn(async move {
+            let _ = server.handle_notification(&session, notification).await;
+        });
+    }
+}
+
+/// The error answered to a request whose id is already in flight, since its response could not be
+/// told apart.
+fn duplicate_request_error(request_id: RequestId) -> JSONRPCError {
+    McpError::invalid_request("The request id is already in flight in the session")
+        .into_response(request_id)
+}
+
+/// Count the response of a request that was not cancelled.
+fn count_answered(
+    answered: &AtomicUsize,
+    response: Option<JsonrpcBatchResponseItem>,
+) -> Option<JsonrpcBatchResponseItem> {
+    if response.is_some() {
+        answered.fetch_add(1, Ordering::Relaxed);
+    }
+    response
+}
+
+/// Handle a request registered as in flight in the session with the token, so that
+/// `notifications/cancelled` can abort it. Returns `None` if it was cancelled.
+///
+/// Requests are registered before their task is spawned, so that shutdown sees every one.
+async fn handle_cancellable(
+    server: &MCPServer,
+    session: &Arc<Session>,
+    request: JSONRPCRequest,
+    token: CancellationToken,
+) -> Option<JsonrpcBatchResponseItem> {
+    let request_id = request.id.clone();
+    // A panicking handler is answered with an internal error rather than never answered
+    let response = async {
+        AssertUnwindSafe(server.handle_request(session, request))
+            .catch_unwind()
+            .await
+            .unwrap_or_else(|_| {
+                JsonrpcBatchResponseItem::JSONRPCError(
+                    McpError::internal("Request handler panicked")
+                        .into_response(request_id.clone()),
+                )
+            })
+    };
+    let result = token.run_until_cancelled(response).await;
+    session.finish_request(&request_id, &token);
+    // Also cancelled if it finished just as the shutdown deadline cancelled it, since the
+    // cancelled request has already been answered
+    result.filter(|_| !token.is_cancelled())
+}
+
+/// Poll the port, or wait forever once the connection is gone.
+async fn progress_while<P: RPCPort>(
+    connected: bool,
+    port: &mut P,
+) -> anyhow::Result<Option<JSONRPCMessage>> {
+    if connected {
+        port.progress().await
+    } else {
+        futures::future::pending().await
+    }
 }
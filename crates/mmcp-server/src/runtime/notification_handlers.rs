@@ -3,11 +3,12 @@ use mmcp_protocol::mcp::{
     RootsListChangedNotification,
 };
 
-use crate::MCPServer;
+use crate::{MCPServer, session::Session};
 
 impl MCPServer {
     pub async fn handle_notification(
         &self,
+        session: &Session,
         notification: mmcp_protocol::mcp::JSONRPCNotification,
     ) -> anyhow::Result<()> {
         let client_notification =
@@ -18,7 +19,7 @@ impl MCPServer {
 
         match client_notification {
             ClientNotification::CancelledNotification(n) => {
-                self.handle_cancelled_notification(n).await
+                self.handle_cancelled_notification(session, n).await
             }
             ClientNotification::InitializedNotification(n) => {
                 self.handle_initialized_notification(n).await
@@ -27,15 +28,19 @@ impl MCPServer {
                 self.handle_progress_notification(n).await
             }
             ClientNotification::RootsListChangedNotification(n) => {
-                self.handle_roots_list_changed_notification(n).await
+                self.handle_roots_list_changed_notification(session, n)
+                    .await
             }
         }
     }
 
     async fn handle_cancelled_notification(
         &self,
-        _notification: CancelledNotification,
+        session: &Session,
+        notification: CancelledNotification,
     ) -> anyhow::Result<()> {
+        // The request may have finished already, in which case the notification is ignored.
+        session.cancel_request(&notification.params.request_id);
         Ok(())
     }
 
@@ -55,8 +60,159 @@ impl MCPServer {
 
     async fn handle_roots_list_changed_notification(
         &self,
+        session: &Session,
         _notification: RootsListChangedNotification,
     ) -> anyhow::Result<()> {
-        Ok(())
+        session.refresh_roots().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use mmcp_protocol::consts::error_codes;
+    use serde_json::{Value, json};
+    use tokio::sync::mpsc;
+
+    use crate::{
+        MCPServer,
+        primitives::tool::IntoToolResult as _,
+        test_utils::{TestClient, TestTool, call_tool},
+    };
+
+    /// Sends on the channel when dropped, i.e. when the tool holding it is aborted.
+    struct DropSignal(mpsc::UnboundedSender<()>);
+
+    impl Drop for DropSignal {
+        fn drop(&mut self) {
+            let _ = self.0.send(());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_notification() {
+        let (dropped_tx, mut dropped_rx) = mpsc::unbounded_channel();
+        let wait = TestTool::new("wait", move |_, _| {
+            let signal = DropSignal(dropped_tx.clone());
+            async move {
+                let _signal = signal;
+                futures::future::pending().await
+            }
+        });
+        let mut client = TestClient::connect(MCPServer::new("test", "0.1.0").add_tool(wait)).await;
+
+        client.send(call_tool(1, "wait")).await;
+        for request_id in [json!(1), json!("unknown")] {
+            client
+                .send(
+                    json!({"jsonrpc": "2.0", "method": "notifications/cancelled", "params": {
+                        "requestId": request_id, "reason": "no longer needed"
+                    }}),
+                )
+                .await;
+        }
+        tokio::time::timeout(Duration::from_secs(5), dropped_rx.recv())
+            .await
+            .expect("the cancelled tool should be aborted");
+
+        // The cancelled request is never responded
+        let response = client
+            .request(json!({"jsonrpc": "2.0", "id": 2, "method": "ping"}))
+            .await;
+        assert_eq!(
+            response,
+            json!({"jsonrpc": "2.0", "id": 2, "result": {"message": "pong"}})
+        );
+    }
+
+    fn cancel(request_id: i64) -> Value {
+        json!({"jsonrpc": "2.0", "method": "notifications/cancelled", "params": {
+            "requestId": request_id
+        }})
+    }
+
+    #[tokio::test]
+    async fn test_reused_request_id() {
+        let (dropped_tx, mut dropped_rx) = mpsc::unbounded_channel();
+        let wait = TestTool::new("wait", move |_, _| {
+            let signal = DropSignal(dropped_tx.clone());
+            async move {
+                let _signal = signal;
+                futures::future::pending().await
+            }
+        });
+        let mut client = TestClient::connect(MCPServer::new("test", "0.1.0").add_tool(wait)).await;
+        let dropped = async |dropped_rx: &mut mpsc::UnboundedReceiver<()>| {
+            tokio::time::timeout(Duration::from_secs(5), dropped_rx.recv())
+                .await
+                .expect("the cancelled tool should be aborted")
+        };
+
+        // An id in flight cannot be reused
+        client.send(call_tool(1, "wait")).await;
+        let response = client.request(call_tool(1, "wait")).await;
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["error"]["code"], error_codes::INVALID_REQUEST);
+
+        // Once cancelled, it can, and the new request can be cancelled as well
+        client.send(cancel(1)).await;
+        dropped(&mut dropped_rx).await;
+        client.send(call_tool(1, "wait")).await;
+        client.send(cancel(1)).await;
+        dropped(&mut dropped_rx).await;
+    }
+
+    /// Answer the `roots/list` request with the roots.
+    async fn answer_roots(client: &mut TestClient, request: &Value, uris: &[&str]) {
+        assert_eq!(request["method"], "roots/list");
+        let roots = uris
+            .iter()
+            .map(|uri| json!({"uri": uri}))
+            .collect::<Vec<_>>();
+        client
+            .send(json!({"jsonrpc": "2.0", "id": request["id"], "result": {"roots": roots}}))
+            .await;
+    }
+
+    /// The roots seen by a tool.
+    async fn tool_roots(client: &mut TestClient) -> Value {
+        let response = client.request(call_tool(1, "roots")).await;
+        response["result"]["content"][0]["text"].clone()
+    }
+
+    #[tokio::test]
+    async fn test_roots_list_changed() {
+        let roots = TestTool::new("roots", |_, ctx| async move {
+            let uris = ctx.roots().into_iter().map(|root| root.uri);
+            uris.collect::<Vec<_>>().join(",").into_tool_result()
+        });
+        let mut client = TestClient::serve(MCPServer::new("test", "0.1.0").add_tool(roots));
+        client
+            .initialize("2025-03-26", json!({"roots": {"listChanged": true}}))
+            .await;
+
+        // The roots are fetched after initialization, and again when they change
+        let initial = client.next().await;
+        client
+            .send(json!({"jsonrpc": "2.0", "method": "notifications/roots/list_changed"}))
+            .await;
+        let changed = client.next().await;
+
+        answer_roots(&mut client, &changed, &["file:///new"]).await;
+        let mut seen = tool_roots(&mut client).await;
+        for _ in 0..100 {
+            if seen == "file:///new" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            seen = tool_roots(&mut client).await;
+        }
+        assert_eq!(seen, "file:///new");
+
+        // The initial fetch answered last is stale, and must not overwrite the newer roots
+        answer_roots(&mut client, &initial, &["file:///old"]).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(tool_roots(&mut client).await, "file:///new");
     }
 }
@@ -1,81 +1,94 @@
+use std::{borrow::Cow, sync::Arc};
+
+use anyhow::Context as _;
 use mmcp_protocol::{
-    consts::error_codes,
+    ProtocolVersion,
     mcp::{
-        self, CallToolRequest, ClientRequest, CompleteRequest, GetPromptRequest, JSONRPCError,
-        JSONRPCRequest, JSONRPCResponse, JsonrpcBatchResponseItem, JsonrpcErrorError,
-        ListPromptsRequest, ListResourcesRequest, ListToolsRequest, PingRequest,
-        ReadResourceRequest, RequestId, SetLevelRequest, SubscribeRequest, UnsubscribeRequest,
+        self, CallToolRequest, CallToolResult, CompleteRequest, CompleteRequestParamsRef,
+        CompleteResult, GetPromptRequest, GetPromptResult, JSONRPCRequest, JSONRPCResponse,
+        JsonrpcBatchResponseItem, ListPromptsRequest, ListResourceTemplatesRequest,
+        ListResourcesRequest, ListToolsRequest, PingRequest, ReadResourceRequest,
+        ReadResourceResult, RequestId, SetLevelRequest, SubscribeRequest, UnsubscribeRequest,
     },
 };
+use serde::de::DeserializeOwned;
 
-use crate::{MCPServer, serialize_tool_call_result};
+use crate::{
+    MCPServer,
+    error::McpError,
+    middleware::{self, ToolCall},
+    primitives::{completion::empty_completion, tool::ToolContext},
+    serialize_result,
+    session::Session,
+};
 
 impl MCPServer {
+    /// Handle a request, answering every failure with a `JSONRPCError`.
     pub async fn handle_request(
         &self,
+        session: &Arc<Session>,
         request: JSONRPCRequest,
-    ) -> anyhow::Result<JsonrpcBatchResponseItem> {
-        let request_id = request.id;
-        let client_request = serde_json::from_value::<ClientRequest>(serde_json::json!({
-            "method": request.method,
-            "params": request.params,
-        }))?;
-
-        match client_request {
-            ClientRequest::InitializeRequest(_) => Err(anyhow::anyhow!(
-                "Unexpected initialize request after initialization"
+    ) -> JsonrpcBatchResponseItem {
+        let request_id = request.id.clone();
+        match self.handle_method(session, request).await {
+            Ok(result) => JsonrpcBatchResponseItem::JSONRPCResponse(JSONRPCResponse {
+                id: request_id,
+                jsonrpc: Default::default(),
+                result,
+                extra: Default::default(),
+            }),
+            Err(error) => JsonrpcBatchResponseItem::JSONRPCError(error.into_response(request_id)),
+        }
+    }
+
+    async fn handle_method(
+        &self,
+        session: &Arc<Session>,
+        request: JSONRPCRequest,
+    ) -> Result<mcp::Result, McpError> {
+        // Dispatched by method rather than by parsing into `ClientRequest`, so that unknown
+        // methods and invalid params can be told apart. The 2025-03-26 `ClientRequest` also omits
+        // `resources/templates/list`.
+        let request_id = request.id.clone();
+        match request.method.as_str() {
+            "initialize" => Err(McpError::invalid_request(
+                "Unexpected initialize request after initialization",
             )),
-            ClientRequest::PingRequest(ping_request) => {
-                self.handle_ping_request(request_id, ping_request).await
-            }
-            ClientRequest::ListResourcesRequest(list_resources_request) => {
-                self.handle_list_resources_request(request_id, list_resources_request)
-                    .await
-            }
-            ClientRequest::ReadResourceRequest(read_resource_request) => {
-                self.handle_read_resource_request(request_id, read_resource_request)
+            "ping" => self.handle_ping_request(parse(request)?).await,
+            "resources/list" => self.handle_list_resources_request(parse(request)?).await,
+            "resources/templates/list" => {
+                self.handle_list_resource_templates_request(parse(request)?)
                     .await
             }
-            ClientRequest::SubscribeRequest(subscribe_request) => {
-                self.handle_subscribe_request(request_id, subscribe_request)
+            "resources/read" => self.handle_read_resource_request(parse(request)?).await,
+            "resources/subscribe" => {
+                self.handle_subscribe_request(session, parse(request)?)
                     .await
             }
-            ClientRequest::UnsubscribeRequest(unsubscribe_request) => {
-                self.handle_unsubscribe_request(request_id, unsubscribe_request)
+            "resources/unsubscribe" => {
+                self.handle_unsubscribe_request(session, parse(request)?)
                     .await
             }
-            ClientRequest::ListPromptsRequest(list_prompts_request) => {
-                self.handle_list_prompts_request(request_id, list_prompts_request)
+            "prompts/list" => self.handle_list_prompts_request(parse(request)?).await,
+            "prompts/get" => self.handle_get_prompt_request(parse(request)?).await,
+            "tools/list" => {
+                self.handle_list_tools_request(session, parse(request)?)
                     .await
             }
-            ClientRequest::GetPromptRequest(get_prompt_request) => {
-                self.handle_get_prompt_request(request_id, get_prompt_request)
+            "tools/call" => {
+                self.handle_call_tool_request(session, request_id, parse(request)?)
                     .await
             }
-            ClientRequest::ListToolsRequest(list_tools_request) => {
-                self.handle_list_tools_request(request_id, list_tools_request)
-                    .await
-            }
-            ClientRequest::CallToolRequest(call_tool_request) => {
-                self.handle_call_tool_request(request_id, call_tool_request)
-                    .await
-            }
-            ClientRequest::SetLevelRequest(set_level_request) => {
-                self.handle_set_level_request(request_id, set_level_request)
-                    .await
-            }
-            ClientRequest::CompleteRequest(complete_request) => {
-                self.handle_complete_request(request_id, complete_request)
+            "logging/setLevel" => {
+                self.handle_set_level_request(session, parse(request)?)
                     .await
             }
+            "completion/complete" => self.handle_complete_request(parse(request)?).await,
+            method => Err(McpError::method_not_found(method)),
         }
     }
 
-    async fn handle_ping_request(
-        &self,
-        request_id: RequestId,
-        _request: PingRequest,
-    ) -> anyhow::Result<JsonrpcBatchResponseItem> {
+    async fn handle_ping_request(&self, _request: PingRequest) -> Result<mcp::Result, McpError> {
         let extra = serde_json::json!({
             "message": "pong"
         })
@@ -83,171 +96,397 @@ impl MCPServer {
         .unwrap()
         .clone();
 
-        Ok(JsonrpcBatchResponseItem::JSONRPCResponse(JSONRPCResponse {
-            id: request_id,
-            jsonrpc: Default::default(),
-            result: mcp::Result {
-                meta: Default::default(),
-                extra,
-            },
-            extra: Default::default(),
-        }))
+        Ok(mcp::Result {
+            meta: Default::default(),
+            extra,
+        })
     }
 
     async fn handle_list_resources_request(
         &self,
-        request_id: RequestId,
         _request: ListResourcesRequest,
-    ) -> anyhow::Result<JsonrpcBatchResponseItem> {
+    ) -> Result<mcp::Result, McpError> {
         let resources = self
             .list_resources()
-            .map(|resource| Ok(serde_json::to_value(resource)?))
-            .collect::<anyhow::Result<Vec<_>>>()?;
-
-        Ok(JsonrpcBatchResponseItem::JSONRPCResponse(JSONRPCResponse {
-            id: request_id,
-            jsonrpc: Default::default(),
-            result: mcp::Result {
-                meta: Default::default(),
-                extra: serde_json::json!({"resources": resources})
-                    .as_object()
-                    .unwrap()
-                    .clone(),
-            },
-            extra: Default::default(),
-        }))
+            .map(|resource| {
+                serde_json::to_value(mcp::Resource {
+                    uri: resource.uri().into_owned(),
+                    name: resource.name().into_owned(),
+                    description: resource.description().map(Cow::into_owned),
+                    mime_type: resource.mime_type().map(Cow::into_owned),
+                    annotations: resource.annotations(),
+                    extra: Default::default(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(mcp::Result {
+            meta: Default::default(),
+            extra: serde_json::json!({"resources": resources})
+                .as_object()
+                .unwrap()
+                .clone(),
+        })
     }
 
     async fn handle_read_resource_request(
         &self,
-        _request_id: RequestId,
-        _request: ReadResourceRequest,
-    ) -> anyhow::Result<JsonrpcBatchResponseItem> {
-        todo!()
+        request: ReadResourceRequest,
+    ) -> Result<mcp::Result, McpError> {
+        let result = match self.stacks.get() {
+            Some(stacks) => middleware::call(&stacks.resources, request).await?,
+            None => self.read_resource(request).await?,
+        };
+        Ok(serialize_result(result)?)
+    }
+
+    pub(crate) async fn read_resource(
+        &self,
+        request: ReadResourceRequest,
+    ) -> Result<ReadResourceResult, McpError> {
+        let uri = request.params.uri.clone();
+        let result = if let Some(resource) = self.get_resource(&uri) {
+            resource.read(request).await
+        } else if let Some((template, variables)) = self.match_resource_template(&uri) {
+            template.read(request, variables).await
+        } else {
+            return Err(McpError::resource_not_found(&uri));
+        };
+
+        // An `McpError` returned by the resource keeps its code through the context
+        Ok(result.context("Failed to read resource")?)
+    }
+
+    async fn handle_list_resource_templates_request(
+        &self,
+        _request: ListResourceTemplatesRequest,
+    ) -> Result<mcp::Result, McpError> {
+        let resource_templates = self
+            .list_resource_templates()
+            .map(|template| {
+                serde_json::to_value(mcp::ResourceTemplate {
+                    uri_template: template.uri_template().into_owned(),
+                    name: template.name().into_owned(),
+                    description: template.description().map(Cow::into_owned),
+                    mime_type: template.mime_type().map(Cow::into_owned),
+                    annotations: template.annotations(),
+                    extra: Default::default(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(mcp::Result {
+            meta: Default::default(),
+            extra: serde_json::json!({"resourceTemplates": resource_templates})
+                .as_object()
+                .unwrap()
+                .clone(),
+        })
     }
 
     async fn handle_subscribe_request(
         &self,
-        _request_id: RequestId,
-        _request: SubscribeRequest,
-    ) -> anyhow::Result<JsonrpcBatchResponseItem> {
-        todo!()
+        session: &Session,
+        request: SubscribeRequest,
+    ) -> Result<mcp::Result, McpError> {
+        let uri = request.params.uri;
+        if self.get_resource(&uri).is_none() && self.match_resource_template(&uri).is_none() {
+            return Err(McpError::resource_not_found(&uri));
+        }
+        session.subscribe(uri);
+
+        Ok(empty_result())
     }
 
     async fn handle_unsubscribe_request(
         &self,
-        _request_id: RequestId,
-        _request: UnsubscribeRequest,
-    ) -> anyhow::Result<JsonrpcBatchResponseItem> {
-        todo!()
+        session: &Session,
+        request: UnsubscribeRequest,
+    ) -> Result<mcp::Result, McpError> {
+        session.unsubscribe(&request.params.uri);
+
+        Ok(empty_result())
     }
 
     async fn handle_list_prompts_request(
         &self,
-        request_id: RequestId,
         _request: ListPromptsRequest,
-    ) -> anyhow::Result<JsonrpcBatchResponseItem> {
+    ) -> Result<mcp::Result, McpError> {
         let prompts = self
             .list_prompts()
-            .map(|prompt| Ok(serde_json::to_value(prompt)?))
-            .collect::<anyhow::Result<Vec<_>>>()?;
-
-        Ok(JsonrpcBatchResponseItem::JSONRPCResponse(JSONRPCResponse {
-            id: request_id,
-            jsonrpc: Default::default(),
-            result: mcp::Result {
-                meta: Default::default(),
-                extra: serde_json::json!({"prompts": prompts})
-                    .as_object()
-                    .unwrap()
-                    .clone(),
-            },
-            extra: Default::default(),
-        }))
+            .map(|prompt| {
+                serde_json::to_value(mcp::Prompt {
+                    name: prompt.name().into_owned(),
+                    description: prompt.description().map(Cow::into_owned),
+                    arguments: Some(prompt.arguments()),
+                    extra: Default::default(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(mcp::Result {
+            meta: Default::default(),
+            extra: serde_json::json!({"prompts": prompts})
+                .as_object()
+                .unwrap()
+                .clone(),
+        })
     }
 
     async fn handle_get_prompt_request(
         &self,
-        _request_id: RequestId,
-        _request: GetPromptRequest,
-    ) -> anyhow::Result<JsonrpcBatchResponseItem> {
-        todo!()
+        request: GetPromptRequest,
+    ) -> Result<mcp::Result, McpError> {
+        let result = match self.stacks.get() {
+            Some(stacks) => middleware::call(&stacks.prompts, request).await?,
+            None => self.render_prompt(request).await?,
+        };
+        Ok(serialize_result(result)?)
+    }
+
+    pub(crate) async fn render_prompt(
+        &self,
+        request: GetPromptRequest,
+    ) -> Result<GetPromptResult, McpError> {
+        let Some(prompt) = self.get_prompt(&request.params.name) else {
+            return Err(McpError::invalid_params(format!(
+                "Prompt not found: {}",
+                request.params.name
+            )));
+        };
+
+        Ok(prompt.get(request).await.context("Failed to get prompt")?)
     }
 
     async fn handle_list_tools_request(
         &self,
-        request_id: RequestId,
+        session: &Session,
         _request: ListToolsRequest,
-    ) -> anyhow::Result<JsonrpcBatchResponseItem> {
+    ) -> Result<mcp::Result, McpError> {
+        let structured = supports_structured_content(session);
         let tools = self
             .list_tools()
+            .into_iter()
             .map(|tool| {
-                Ok(serde_json::json!({
+                let mut value = serde_json::json!({
                     "name": tool.name(),
                     "description": tool.description(),
                     "inputSchema": serde_json::from_str::<serde_json::Value>(tool.input_schema().as_ref())?,
                     "annotations": tool.annotations()
-                }))
+                });
+                if let Some(output_schema) = tool.output_schema().filter(|_| structured) {
+                    value["outputSchema"] = serde_json::from_str(output_schema.as_ref())?;
+                }
+                Ok(value)
             })
-            .collect::<anyhow::Result<Vec<_>>>()?;
-
-        Ok(JsonrpcBatchResponseItem::JSONRPCResponse(JSONRPCResponse {
-            id: request_id,
-            jsonrpc: Default::default(),
-            result: mcp::Result {
-                meta: Default::default(),
-                extra: serde_json::json!({"tools": tools})
-                    .as_object()
-                    .unwrap()
-                    .clone(),
-            },
-            extra: Default::default(),
-        }))
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+
+        Ok(mcp::Result {
+            meta: Default::default(),
+            extra: serde_json::json!({"tools": tools})
+                .as_object()
+                .unwrap()
+                .clone(),
+        })
     }
 
     async fn handle_call_tool_request(
         &self,
+        session: &Arc<Session>,
         request_id: RequestId,
         request: CallToolRequest,
-    ) -> anyhow::Result<JsonrpcBatchResponseItem> {
-        let tool_name = request.params.name.as_str();
-
-        if let Some(tool) = self.get_tool(tool_name) {
-            let result = tool.execute(request).await;
-            Ok(JsonrpcBatchResponseItem::JSONRPCResponse(JSONRPCResponse {
-                id: request_id,
-                jsonrpc: Default::default(),
-                result: serialize_tool_call_result(result)?,
-                extra: Default::default(),
-            }))
-        } else {
-            Ok(JsonrpcBatchResponseItem::JSONRPCError(JSONRPCError {
-                error: JsonrpcErrorError {
-                    message: format!("Tool not found: {}", tool_name),
-                    code: error_codes::INVALID_PARAMS,
-                    data: None,
-                    extra: Default::default(),
-                },
-                id: request_id,
-                jsonrpc: Default::default(),
-                extra: Default::default(),
-            }))
+    ) -> Result<mcp::Result, McpError> {
+        let context = ToolContext::new(
+            request_id.clone(),
+            &request,
+            Arc::clone(session),
+            session
+                .request_cancellation_token(&request_id)
+                .unwrap_or_default(),
+            Arc::clone(&self.states),
+        );
+        let mut result = match self.stacks.get() {
+            Some(stacks) => middleware::call(&stacks.tools, ToolCall { request, context }).await?,
+            None => self.call_tool(request, context).await?,
+        };
+        if !supports_structured_content(session) {
+            result.extra.remove("structuredContent");
         }
+        Ok(serialize_result(result)?)
+    }
+
+    pub(crate) async fn call_tool(
+        &self,
+        request: CallToolRequest,
+        context: ToolContext,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(tool) = self.get_tool(&request.params.name) else {
+            return Err(McpError::invalid_params(format!(
+                "Tool not found: {}",
+                request.params.name
+            )));
+        };
+
+        Ok(tool.execute(request, context).await)
     }
 
     async fn handle_set_level_request(
         &self,
-        _request_id: RequestId,
-        _request: SetLevelRequest,
-    ) -> anyhow::Result<JsonrpcBatchResponseItem> {
-        todo!()
+        session: &Session,
+        request: SetLevelRequest,
+    ) -> Result<mcp::Result, McpError> {
+        session.set_log_level(request.params.level);
+
+        Ok(empty_result())
     }
 
     async fn handle_complete_request(
         &self,
-        _request_id: RequestId,
-        _request: CompleteRequest,
-    ) -> anyhow::Result<JsonrpcBatchResponseItem> {
-        todo!()
+        request: CompleteRequest,
+    ) -> Result<mcp::Result, McpError> {
+        let argument = request.params.argument;
+        let completer = match &request.params.r#ref {
+            CompleteRequestParamsRef::PromptReference(reference) => {
+                let Some(prompt) = self.get_prompt(&reference.name) else {
+                    return Err(McpError::invalid_params(format!(
+                        "Prompt not found: {}",
+                        reference.name
+                    )));
+                };
+                prompt.completer(&argument.name)
+            }
+            CompleteRequestParamsRef::ResourceReference(reference) => {
+                let template = self
+                    .list_resource_templates()
+                    .find(|template| template.uri_template() == reference.uri.as_str());
+                match template {
+                    Some(template) => template.completer(&argument.name),
+                    // Static resources have no variables to complete
+                    None if self.get_resource(&reference.uri).is_some() => None,
+                    None => return Err(McpError::resource_not_found(&reference.uri)),
+                }
+            }
+        };
+
+        let completion = match completer {
+            Some(completer) => completer.complete(argument.value).await,
+            None => empty_completion(),
+        };
+
+        Ok(serialize_result(CompleteResult {
+            completion,
+            meta: None,
+            extra: Default::default(),
+        })?)
+    }
+}
+
+/// Parse the typed request from the method and params, answering bad params with
+/// `INVALID_PARAMS`.
+fn parse<R: DeserializeOwned>(request: JSONRPCRequest) -> Result<R, McpError> {
+    serde_json::from_value(serde_json::json!({
+        "method": request.method,
+        "params": request.params,
+    }))
+    .map_err(|e| McpError::invalid_params(format!("Invalid params for {}: {e}", request.method)))
+}
+
+fn supports_structured_content(session: &Session) -> bool {
+    session
+        .protocol_version()
+        .is_some_and(ProtocolVersion::supports_structured_content)
+}
+
+fn empty_result() -> mcp::Result {
+    mcp::Result {
+        meta: Default::default(),
+        extra: Default::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mmcp_protocol::consts::error_codes;
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        VersionPolicy,
+        primitives::tool::{IntoToolResult as _, Json},
+        test_utils::{TestClient, TestTool, call_tool},
+    };
+
+    #[tokio::test]
+    async fn test_error_responses() {
+        let server = MCPServer::new("test", "0.1.0")
+            .add_tool(TestTool::new("panic", |_, _| async {
+                panic!("tool panicked")
+            }));
+        let mut client = TestClient::serve(server);
+
+        let response = client
+            .request(json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}))
+            .await;
+        assert_eq!(response["error"]["code"], error_codes::INVALID_PARAMS);
+        let result = client.initialize("2025-03-26", json!({})).await;
+        assert_eq!(result["protocolVersion"], "2025-03-26");
+
+        let response = client
+            .request(json!({"jsonrpc": "2.0", "id": 2, "method": "unknown"}))
+            .await;
+        assert_eq!(response["id"], 2);
+        assert_eq!(response["error"]["code"], error_codes::METHOD_NOT_FOUND);
+
+        let response = client
+            .request(json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {}}))
+            .await;
+        assert_eq!(response["error"]["code"], error_codes::INVALID_PARAMS);
+
+        let response = client.request(call_tool(4, "panic")).await;
+        assert_eq!(response["error"]["code"], error_codes::INTERNAL_ERROR);
+
+        let response = client
+            .request(
+                json!({"jsonrpc": "2.0", "id": 5, "method": "resources/read", "params": {
+                    "uri": "file:///missing"
+                }}),
+            )
+            .await;
+        assert_eq!(response["error"]["code"], error_codes::RESOURCE_NOT_FOUND);
+    }
+
+    /// List the tools and call `point` on the protocol version, returning both results.
+    async fn list_and_call(protocol_version: &str) -> (Value, Value) {
+        let point = TestTool::new("point", |_, _| async {
+            Json(json!({"x": 1})).into_tool_result()
+        })
+        .with_output_schema(r#"{"type": "object", "properties": {"x": {"type": "integer"}}}"#);
+        let server = MCPServer::new("test", "0.1.0")
+            .add_tool(point)
+            .with_version_policy(
+                VersionPolicy::default()
+                    .supported([ProtocolVersion::V20250326, ProtocolVersion::V20250618]),
+            );
+        let mut client = TestClient::serve(server);
+        client.initialize(protocol_version, json!({})).await;
+
+        let list = client
+            .request(json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}))
+            .await;
+        let call = client.request(call_tool(3, "point")).await;
+        (list["result"]["tools"][0].clone(), call["result"].clone())
+    }
+
+    #[tokio::test]
+    async fn test_structured_content() {
+        let (tool, result) = list_and_call("2025-06-18").await;
+        assert_eq!(tool["outputSchema"]["type"], "object");
+        assert_eq!(result["structuredContent"], json!({"x": 1}));
+        assert_eq!(result["content"][0]["text"], r#"{"x":1}"#);
+
+        let (tool, result) = list_and_call("2025-03-26").await;
+        assert!(tool.get("outputSchema").is_none());
+        assert!(result.get("structuredContent").is_none());
+        assert_eq!(result["content"][0]["text"], r#"{"x":1}"#);
     }
 }
@@ -0,0 +1,215 @@
+use std::{sync::Arc, time::Duration};
+
+use mmcp_protocol::{
+    mcp::{
+        CreateMessageRequestParams, CreateMessageRequestParamsIncludeContext, CreateMessageResult,
+        JsonrpcErrorError, ModelPreferences, Role, SamplingMessage, SamplingMessageContent,
+        TextContent,
+    },
+    port::{RPCSink as _, RequestOptions},
+};
+
+use crate::session::Session;
+
+/// The `maxTokens` sent when [`Sampling::max_tokens`] is not called.
+pub const DEFAULT_MAX_TOKENS: i64 = 1024;
+
+/// How long to wait for the client when [`Sampling::timeout`] is not called. Longer than the
+/// timeout of other requests, since the client may ask the user to approve the request.
+pub const DEFAULT_SAMPLING_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SamplingError {
+    #[error("the client does not support sampling")]
+    NotSupported,
+    #[error("the client rejected the sampling request: {}", .0.message)]
+    Rejected(JsonrpcErrorError),
+    #[error(transparent)]
+    Rpc(#[from] anyhow::Error),
+}
+
+/// A `sampling/createMessage` request to the client, asking it for an LLM completion.
+///
+/// ```ignore
+/// let result = ctx
+///     .sample()
+///     .system_prompt("You summarize text in one sentence.")
+///     .user(text)
+///     .max_tokens(200)
+///     .send()
+///     .await?;
+/// ```
+pub struct Sampling {
+    session: Arc<Session>,
+    params: CreateMessageRequestParams,
+    options: RequestOptions,
+}
+
+impl Sampling {
+    pub(crate) fn new(session: Arc<Session>) -> Self {
+        Self {
+            session,
+            params: CreateMessageRequestParams {
+                include_context: None,
+                max_tokens: DEFAULT_MAX_TOKENS,
+                messages: Vec::new(),
+                metadata: None,
+                model_preferences: None,
+                stop_sequences: None,
+                system_prompt: None,
+                temperature: None,
+                extra: Default::default(),
+            },
+            options: RequestOptions::default().timeout(DEFAULT_SAMPLING_TIMEOUT),
+        }
+    }
+
+    /// Append a message to the conversation.
+    pub fn message(mut self, message: SamplingMessage) -> Self {
+        self.params.messages.push(message);
+        self
+    }
+
+    /// Append a text message from the user.
+    pub fn user(self, text: impl Into<String>) -> Self {
+        self.text(Role::User, text.into())
+    }
+
+    /// Append a text message from the assistant.
+    pub fn assistant(self, text: impl Into<String>) -> Self {
+        self.text(Role::Assistant, text.into())
+    }
+
+    fn text(self, role: Role, text: String) -> Self {
+        self.message(SamplingMessage {
+            content: SamplingMessageContent::TextContent(TextContent {
+                annotations: None,
+                text,
+                r#type: Default::default(),
+                extra: Default::default(),
+            }),
+            role,
+            extra: Default::default(),
+        })
+    }
+
+    pub fn system_prompt(mut self, system_prompt: impl Into<String>) -> Self {
+        self.params.system_prompt = Some(system_prompt.into());
+        self
+    }
+
+    pub fn max_tokens(mut self, max_tokens: i64) -> Self {
+        self.params.max_tokens = max_tokens;
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        self.params.temperature = Some(temperature);
+        self
+    }
+
+    pub fn stop_sequences(mut self, stop_sequences: impl IntoIterator<Item = String>) -> Self {
+        self.params.stop_sequences = Some(stop_sequences.into_iter().collect());
+        self
+    }
+
+    pub fn model_preferences(mut self, model_preferences: ModelPreferences) -> Self {
+        self.params.model_preferences = Some(model_preferences);
+        self
+    }
+
+    pub fn include_context(
+        mut self,
+        include_context: CreateMessageRequestParamsIncludeContext,
+    ) -> Self {
+        self.params.include_context = Some(include_context);
+        self
+    }
+
+    pub fn metadata(mut self, metadata: serde_json::Map<String, serde_json::Value>) -> Self {
+        self.params.metadata = Some(metadata);
+        self
+    }
+
+    /// Give up waiting for the client after the timeout, [`DEFAULT_SAMPLING_TIMEOUT`] by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.options = self.options.timeout(timeout);
+        self
+    }
+
+    /// Send the request, failing with [`SamplingError::NotSupported`] if the client did not
+    /// advertise the `sampling` capability.
+    pub async fn send(self) -> Result<CreateMessageResult, SamplingError> {
+        let supported = self
+            .session
+            .client_capabilities()
+            .is_some_and(|capabilities| capabilities.sampling.is_some());
+        if !supported {
+            return Err(SamplingError::NotSupported);
+        }
+        self.session
+            .peer()
+            .clone()
+            .request_with_options("sampling/createMessage", self.params, self.options)
+            .await?
+            .map_err(|error| SamplingError::Rejected(error.error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mmcp_protocol::mcp::CreateMessageResultContent;
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        MCPServer,
+        primitives::tool::IntoToolResult as _,
+        test_utils::{TestClient, TestTool, call_tool},
+    };
+
+    /// Summarize with the client's LLM
+    fn summarize() -> TestTool {
+        TestTool::new("summarize", |_, context| async move {
+            let result = context.sample().user("Hello").max_tokens(10).send().await;
+            match result.map(|result| result.content) {
+                Ok(CreateMessageResultContent::TextContent(text)) => text.text,
+                Ok(_) => "unexpected content".into(),
+                Err(e) => e.to_string(),
+            }
+            .into_tool_result()
+        })
+    }
+
+    async fn call_summarize(capabilities: Value) -> Value {
+        let mut client = TestClient::serve(MCPServer::new("test", "0.1.0").add_tool(summarize()));
+        client.initialize("2025-03-26", capabilities).await;
+        client.send(call_tool(2, "summarize")).await;
+
+        loop {
+            let message = client.next().await;
+            if message["method"] == "sampling/createMessage" {
+                assert_eq!(message["params"]["maxTokens"], 10);
+                assert_eq!(message["params"]["messages"][0]["content"]["text"], "Hello");
+                client
+                    .send(json!({"jsonrpc": "2.0", "id": message["id"], "result": {
+                        "content": {"type": "text", "text": "Hi"},
+                        "model": "test-model",
+                        "role": "assistant"
+                    }}))
+                    .await;
+            } else if message["id"] == 2 {
+                return message["result"]["content"][0]["text"].clone();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sampling() {
+        assert_eq!(call_summarize(json!({"sampling": {}})).await, "Hi");
+        assert_eq!(
+            call_summarize(json!({})).await,
+            SamplingError::NotSupported.to_string()
+        );
+    }
+}
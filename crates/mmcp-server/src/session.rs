@@ -0,0 +1,344 @@
+use std::{
+    collections::{HashMap, HashSet, hash_map::Entry},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+use futures::{
+    SinkExt as _, StreamExt as _,
+    channel::{mpsc, oneshot},
+};
+use mmcp_protocol::{
+    ProtocolVersion,
+    mcp::{
+        ClientCapabilities, Implementation, ListRootsResult, LoggingLevel,
+        LoggingMessageNotificationParams, RequestId, Root, ServerCapabilities,
+    },
+    port::RPCSink as _,
+};
+pub use mmcp_rpc::Peer;
+use tokio::runtime::Handle;
+use tokio_util::sync::CancellationToken;
+
+use crate::logging::severity;
+
+/// How many log messages may wait to be sent to a client before new ones are dropped.
+const LOG_QUEUE_CAPACITY: usize = 1024;
+
+/// A log message waiting to be sent, with the sender told once it is sent if anyone waits for it.
+type QueuedLog = (
+    LoggingMessageNotificationParams,
+    Option<oneshot::Sender<()>>,
+);
+
+/// Identifies a connected session. Unique within the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SessionId(u64);
+
+impl SessionId {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl std::fmt::Display for SessionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// State of a single client connection.
+pub struct Session {
+    id: SessionId,
+    peer: Peer,
+    client_info: OnceLock<Implementation>,
+    client_capabilities: OnceLock<ClientCapabilities>,
+    protocol_version: OnceLock<ProtocolVersion>,
+    server_capabilities: OnceLock<ServerCapabilities>,
+    initialized: AtomicBool,
+    /// The roots with the number of the refresh that fetched them
+    roots: Mutex<Option<(u64, Vec<Root>)>>,
+    /// The number of roots refreshes started, so that a slow one cannot overwrite a newer one
+    roots_refreshes: AtomicU64,
+    subscriptions: Mutex<HashSet<String>>,
+    log_level: Mutex<Option<LoggingLevel>>,
+    /// Log messages sent in order by a single task, started with the first message
+    log_queue: OnceLock<mpsc::Sender<QueuedLog>>,
+    in_flight: Mutex<HashMap<RequestId, CancellationToken>>,
+}
+
+impl Session {
+    pub(crate) fn new(peer: Peer) -> Self {
+        Self {
+            id: SessionId::next(),
+            peer,
+            client_info: Default::default(),
+            client_capabilities: Default::default(),
+            protocol_version: Default::default(),
+            server_capabilities: Default::default(),
+            initialized: Default::default(),
+            roots: Default::default(),
+            roots_refreshes: Default::default(),
+            subscriptions: Default::default(),
+            log_level: Default::default(),
+            log_queue: Default::default(),
+            in_flight: Default::default(),
+        }
+    }
+
+    pub fn id(&self) -> SessionId {
+        self.id
+    }
+
+    /// The sink for sending messages to the client of this session.
+    pub fn peer(&self) -> &Peer {
+        &self.peer
+    }
+
+    /// The name and version of the client, or `None` before `initialize`.
+    pub fn client_info(&self) -> Option<&Implementation> {
+        self.client_info.get()
+    }
+
+    /// The capabilities the client advertised in `initialize`, or `None` before it.
+    pub fn client_capabilities(&self) -> Option<&ClientCapabilities> {
+        self.client_capabilities.get()
+    }
+
+    /// The protocol version negotiated in `initialize`, or `None` before it.
+    pub fn protocol_version(&self) -> Option<ProtocolVersion> {
+        self.protocol_version.get().copied()
+    }
+
+    /// The capabilities the server advertised in its `initialize` response, or `None` before it.
+    pub fn server_capabilities(&self) -> Option<&ServerCapabilities> {
+        self.server_capabilities.get()
+    }
+
+    /// Whether the client sent `notifications/initialized`, after which the server may send
+    /// requests and notifications other than pings and logging.
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn set_client(
+        &self,
+        info: Implementation,
+        capabilities: ClientCapabilities,
+        protocol_version: ProtocolVersion,
+        server_capabilities: ServerCapabilities,
+    ) {
+        let _ = self.client_info.set(info);
+        let _ = self.client_capabilities.set(capabilities);
+        let _ = self.protocol_version.set(protocol_version);
+        let _ = self.server_capabilities.set(server_capabilities);
+    }
+
+    pub(crate) fn set_initialized(&self) {
+        self.initialized.store(true, Ordering::Release);
+    }
+
+    /// Whether the client advertised the `roots` capability.
+    pub fn supports_roots(&self) -> bool {
+        self.client_capabilities()
+            .is_some_and(|capabilities| capabilities.roots.is_some())
+    }
+
+    /// The roots last fetched from the client with `roots/list`, or `None` if they have not been
+    /// fetched or the client does not support roots.
+    pub fn roots(&self) -> Option<Vec<Root>> {
+        self.roots
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(_, roots)| roots.clone())
+    }
+
+    /// Fetch the roots from the client with `roots/list` and cache them. Called after
+    /// initialization and on `notifications/roots/list_changed`. If refreshes overlap, the roots
+    /// fetched by the latest one are kept.
+    pub async fn refresh_roots(&self) -> anyhow::Result<()> {
+        if !self.supports_roots() {
+            return Ok(());
+        }
+        let refresh = self.roots_refreshes.fetch_add(1, Ordering::Relaxed) + 1;
+        let result: ListRootsResult = self
+            .peer
+            .clone()
+            .request("roots/list", serde_json::Value::Null)
+            .await?
+            .map_err(|error| anyhow::anyhow!("roots/list failed: {}", error.error.message))?;
+        let mut roots = self.roots.lock().unwrap();
+        if roots
+            .as_ref()
+            .is_none_or(|(fetched_by, _)| *fetched_by < refresh)
+        {
+            *roots = Some((refresh, result.roots));
+        }
+        Ok(())
+    }
+
+    /// Subscribe to `notifications/resources/updated` for the URI.
+    pub fn subscribe(&self, uri: impl Into<String>) {
+        self.subscriptions.lock().unwrap().insert(uri.into());
+    }
+
+    /// Unsubscribe from `notifications/resources/updated` for the URI.
+    pub fn unsubscribe(&self, uri: &str) {
+        self.subscriptions.lock().unwrap().remove(uri);
+    }
+
+    /// Whether the client subscribed to updates of the URI.
+    pub fn is_subscribed(&self, uri: &str) -> bool {
+        self.subscriptions.lock().unwrap().contains(uri)
+    }
+
+    /// The minimum level of log messages the client wants to receive, or `None` if it has not sent
+    /// `logging/setLevel` yet.
+    pub fn log_level(&self) -> Option<LoggingLevel> {
+        self.log_level.lock().unwrap().clone()
+    }
+
+    pub fn set_log_level(&self, level: LoggingLevel) {
+        *self.log_level.lock().unwrap() = Some(level);
+    }
+
+    /// Queue a log message to be sent as `notifications/message`, in the order they are queued.
+    /// The message is dropped if the client is too slow to keep up.
+    pub(crate) fn queue_log(&self, runtime: &Handle, params: LoggingMessageNotificationParams) {
+        let _ = self.log_queue(runtime).try_send((params, None));
+    }
+
+    /// Queue a log message like [`queue_log`](Self::queue_log), but wait for room in the queue
+    /// and until the message is sent, so that it arrives before anything sent afterwards.
+    pub(crate) async fn send_log(
+        &self,
+        params: LoggingMessageNotificationParams,
+    ) -> anyhow::Result<()> {
+        let (sent_tx, sent_rx) = oneshot::channel();
+        self.log_queue(&Handle::current())
+            .send((params, Some(sent_tx)))
+            .await
+            .map_err(|_| anyhow::anyhow!("session closed"))?;
+        sent_rx
+            .await
+            .map_err(|_| anyhow::anyhow!("failed to send the log message"))
+    }
+
+    /// The queue of log messages, sent in order by a task started with the first message.
+    fn log_queue(&self, runtime: &Handle) -> mpsc::Sender<QueuedLog> {
+        self.log_queue
+            .get_or_init(|| {
+                let (tx, mut rx) = mpsc::channel::<QueuedLog>(LOG_QUEUE_CAPACITY);
+                let mut peer = self.peer.clone();
+                runtime.spawn(async move {
+                    while let Some((params, sent)) = rx.next().await {
+                        if peer
+                            .send_notification("notifications/message", params)
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                        if let Some(sent) = sent {
+                            let _ = sent.send(());
+                        }
+                    }
+                });
+                tx
+            })
+            .clone()
+    }
+
+    /// Register a request from the client as in flight, returning the token cancelled by
+    /// `notifications/cancelled`, or `None` if a request with the same id is already in flight.
+    pub(crate) fn start_request(&self, request_id: RequestId) -> Option<CancellationToken> {
+        match self.in_flight.lock().unwrap().entry(request_id) {
+            Entry::Occupied(_) => None,
+            Entry::Vacant(entry) => Some(entry.insert(CancellationToken::new()).clone()),
+        }
+    }
+
+    /// Unregister a finished request. A cancelled request was already unregistered, and its id may
+    /// have been reused by a new request since, which is left in flight.
+    pub(crate) fn finish_request(&self, request_id: &RequestId, token: &CancellationToken) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        // An uncancelled request is still registered, since ids in flight are not reused. The
+        // entry of a request cancelled without unregistering it is cancelled as well.
+        let registered = in_flight
+            .get(request_id)
+            .is_some_and(|registered| !token.is_cancelled() || registered.is_cancelled());
+        if registered {
+            in_flight.remove(request_id);
+        }
+    }
+
+    /// The cancellation token of an in-flight request from the client.
+    pub fn request_cancellation_token(&self, request_id: &RequestId) -> Option<CancellationToken> {
+        self.in_flight.lock().unwrap().get(request_id).cloned()
+    }
+
+    /// Cancel an in-flight request from the client. Returns `false` if the request is unknown or
+    /// already finished.
+    pub fn cancel_request(&self, request_id: &RequestId) -> bool {
+        match self.in_flight.lock().unwrap().remove(request_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The number of requests from the client currently in flight.
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight.lock().unwrap().len()
+    }
+
+    /// Cancel every in-flight request, returning their ids.
+    pub(crate) fn cancel_all_requests(&self) -> Vec<RequestId> {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(request_id, token)| {
+                token.cancel();
+                request_id
+            })
+            .collect()
+    }
+
+    /// Whether a log message at the level should be sent to the client.
+    pub fn is_log_enabled(&self, level: &LoggingLevel) -> bool {
+        self.log_level()
+            .is_some_and(|min| severity(level) >= severity(&min))
+    }
+}
+
+/// The sessions currently connected to a server. Cloning gives another handle to the same set.
+#[derive(Clone, Default)]
+pub struct Sessions {
+    sessions: Arc<Mutex<HashMap<SessionId, Arc<Session>>>>,
+}
+
+impl Sessions {
+    pub(crate) fn insert(&self, session: Arc<Session>) {
+        self.sessions.lock().unwrap().insert(session.id(), session);
+    }
+
+    pub(crate) fn remove(&self, id: SessionId) {
+        self.sessions.lock().unwrap().remove(&id);
+    }
+
+    pub fn get(&self, id: SessionId) -> Option<Arc<Session>> {
+        self.sessions.lock().unwrap().get(&id).cloned()
+    }
+
+    /// A snapshot of the connected sessions.
+    pub fn list(&self) -> Vec<Arc<Session>> {
+        self.sessions.lock().unwrap().values().cloned().collect()
+    }
+}
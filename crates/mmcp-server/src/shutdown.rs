@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+/// How long in-flight requests are waited for on shutdown when
+/// [`MCPServer::with_shutdown_timeout`](crate::MCPServer::with_shutdown_timeout) is not called.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What happened to the requests of a session while it was shutting down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShutdownSummary {
+    /// Requests in flight at shutdown that were answered before the deadline.
+    pub completed: usize,
+    /// Requests still in flight at the deadline. They were cancelled and answered with an error.
+    pub abandoned: usize,
+    /// Requests received while draining, answered with an error without being handled.
+    pub rejected: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use mmcp_protocol::consts::error_codes;
+    use serde_json::json;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::{
+        MCPServer,
+        test_utils::{TestClient, call_tool, sleep},
+    };
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let server = MCPServer::new("test", "0.1.0")
+            .add_tool(sleep("100"))
+            .add_tool(sleep("60000"))
+            .with_shutdown_timeout(Duration::from_millis(500));
+        let token = CancellationToken::new();
+        let (mut client, port) = TestClient::pair();
+        let server =
+            tokio::spawn(server.start_with_shutdown(port, token.clone().cancelled_owned()));
+
+        // The `roots/list` requests are never answered
+        client
+            .initialize("2025-03-26", json!({"roots": {"listChanged": true}}))
+            .await;
+        for (id, name) in [(2, "100"), (3, "60000"), (5, "60000")] {
+            client.send(call_tool(id, name)).await;
+        }
+
+        // Let the calls be dispatched before shutting down
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        token.cancel();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        for value in [
+            json!({"jsonrpc": "2.0", "id": 4, "method": "ping"}),
+            json!({"jsonrpc": "2.0", "method": "notifications/cancelled", "params": {
+                "requestId": 5
+            }}),
+            // Refreshing the roots waits for a response while draining, which must not block it
+            json!({"jsonrpc": "2.0", "method": "notifications/roots/list_changed"}),
+        ] {
+            client.send(value).await;
+        }
+
+        let mut responses = Vec::new();
+        for _ in 0..3 {
+            responses.push(client.response().await);
+        }
+        responses.sort_by_key(|response| response["id"].as_i64());
+        assert_eq!(responses[0]["id"], 2);
+        assert_eq!(responses[0]["result"]["content"][0]["text"], "done");
+        assert_eq!(responses[1]["id"], 3);
+        assert_eq!(responses[1]["error"]["code"], error_codes::INTERNAL_ERROR);
+        assert_eq!(responses[2]["id"], 4);
+        assert_eq!(responses[2]["error"]["code"], error_codes::INTERNAL_ERROR);
+
+        let summary = tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("shutdown did not finish")
+            .unwrap()
+            .unwrap();
+        // The request cancelled by the client is neither completed nor abandoned
+        assert_eq!(
+            summary,
+            ShutdownSummary {
+                completed: 1,
+                abandoned: 1,
+                rejected: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connection_closed_without_shutdown() {
+        let server = MCPServer::new("test", "0.1.0").add_tool(sleep("60000"));
+        let (mut client, port) = TestClient::pair();
+        let server = tokio::spawn(server.start(port));
+        client.initialize("2025-03-26", json!({})).await;
+        client.send(call_tool(1, "60000")).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // The running tool is not waited for
+        drop(client);
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("the server should stop when the connection closes")
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,177 @@
+//! An in-memory client for testing the server, and tools shared by the tests.
+
+use std::{borrow::Cow, pin::Pin, sync::Arc, time::Duration};
+
+use futures::{SinkExt as _, StreamExt as _, channel::mpsc, future::BoxFuture};
+use mmcp_protocol::mcp::{CallToolRequest, CallToolResult, JSONRPCMessage, ToolAnnotations};
+use mmcp_rpc::RPCRuntime;
+use serde_json::{Value, json};
+
+use crate::{
+    MCPServer,
+    primitives::tool::{IntoToolResult as _, Tool, ToolContext},
+};
+
+/// The port of a server connected to a [`TestClient`].
+pub(crate) type TestPort =
+    RPCRuntime<mpsc::Sender<JSONRPCMessage>, mpsc::Receiver<anyhow::Result<JSONRPCMessage>>>;
+
+/// The client side of an in-memory connection, exchanging messages as JSON values.
+pub(crate) struct TestClient {
+    tx: mpsc::Sender<anyhow::Result<JSONRPCMessage>>,
+    rx: mpsc::Receiver<JSONRPCMessage>,
+}
+
+impl TestClient {
+    /// A client and the port of the server connected to it.
+    pub(crate) fn pair() -> (Self, TestPort) {
+        let (server_tx, rx) = mpsc::channel(100);
+        let (tx, server_rx) = mpsc::channel(100);
+        (Self { tx, rx }, RPCRuntime::new(server_tx, server_rx))
+    }
+
+    /// Serve the server to a new client, which has not initialized yet.
+    pub(crate) fn serve(server: impl Into<Arc<MCPServer>>) -> Self {
+        let (client, port) = Self::pair();
+        tokio::spawn(server.into().serve(port));
+        client
+    }
+
+    /// Serve the server to a new client initialized on 2025-03-26 without capabilities.
+    pub(crate) async fn connect(server: impl Into<Arc<MCPServer>>) -> Self {
+        let mut client = Self::serve(server);
+        client.initialize("2025-03-26", json!({})).await;
+        client
+    }
+
+    /// Send `initialize` and then `notifications/initialized`, returning the result of
+    /// `initialize`.
+    pub(crate) async fn initialize(
+        &mut self,
+        protocol_version: &str,
+        capabilities: Value,
+    ) -> Value {
+        let response = self
+            .request(
+                json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {
+                    "capabilities": capabilities,
+                    "clientInfo": {"name": "test-client", "version": "0.1.0"},
+                    "protocolVersion": protocol_version
+                }}),
+            )
+            .await;
+        self.send(json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .await;
+        response["result"].clone()
+    }
+
+    pub(crate) async fn send(&mut self, message: Value) {
+        let message = serde_json::from_value(message).unwrap();
+        self.tx.send(Ok(message)).await.unwrap();
+    }
+
+    /// The next message from the server.
+    pub(crate) async fn next(&mut self) -> Value {
+        let message = tokio::time::timeout(Duration::from_secs(5), self.rx.next())
+            .await
+            .expect("no message from the server")
+            .expect("the server closed the connection");
+        serde_json::to_value(message).unwrap()
+    }
+
+    /// The next response from the server, skipping its requests and notifications.
+    pub(crate) async fn response(&mut self) -> Value {
+        loop {
+            let message = self.next().await;
+            if message.get("method").is_none() {
+                return message;
+            }
+        }
+    }
+
+    /// Send a request and wait for the next response.
+    pub(crate) async fn request(&mut self, request: Value) -> Value {
+        self.send(request).await;
+        self.response().await
+    }
+}
+
+/// A `tools/call` request for the tool without arguments.
+pub(crate) fn call_tool(id: i64, name: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "method": "tools/call", "params": {
+        "name": name, "arguments": {}
+    }})
+}
+
+type Execute =
+    Box<dyn Fn(CallToolRequest, ToolContext) -> BoxFuture<'static, CallToolResult> + Send + Sync>;
+
+/// A tool taking any arguments and running a closure.
+pub(crate) struct TestTool {
+    name: &'static str,
+    output_schema: Option<&'static str>,
+    execute: Execute,
+}
+
+impl TestTool {
+    pub(crate) fn new<F, Fut>(name: &'static str, execute: F) -> Self
+    where
+        F: Fn(CallToolRequest, ToolContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CallToolResult> + Send + 'static,
+    {
+        Self {
+            name,
+            output_schema: None,
+            execute: Box::new(move |request, context| Box::pin(execute(request, context))),
+        }
+    }
+
+    pub(crate) fn with_output_schema(mut self, output_schema: &'static str) -> Self {
+        self.output_schema = Some(output_schema);
+        self
+    }
+}
+
+impl Tool for TestTool {
+    fn name(&self) -> Cow<'static, str> {
+        self.name.into()
+    }
+
+    fn description(&self) -> Cow<'static, str> {
+        format!("The {} tool", self.name).into()
+    }
+
+    fn input_schema(&self) -> Cow<'static, str> {
+        r#"{"type": "object"}"#.into()
+    }
+
+    fn output_schema(&self) -> Option<Cow<'static, str>> {
+        self.output_schema.map(Into::into)
+    }
+
+    fn annotations(&self) -> ToolAnnotations {
+        Default::default()
+    }
+
+    fn execute(
+        &self,
+        request: CallToolRequest,
+        context: ToolContext,
+    ) -> Pin<Box<dyn Future<Output = CallToolResult> + Send + '_>> {
+        (self.execute)(request, context)
+    }
+}
+
+/// A tool returning its name.
+pub(crate) fn noop(name: &'static str) -> TestTool {
+    TestTool::new(name, move |_, _| async move { name.into_tool_result() })
+}
+
+/// A tool sleeping for the milliseconds given as its name, then returning `done`.
+pub(crate) fn sleep(millis: &'static str) -> TestTool {
+    let duration = Duration::from_millis(millis.parse().unwrap());
+    TestTool::new(millis, move |_, _| async move {
+        tokio::time::sleep(duration).await;
+        "done".into_tool_result()
+    })
+}
@@ -0,0 +1,108 @@
+use mmcp_protocol::ProtocolVersion;
+
+/// How the server picks the protocol version of a session from the one requested in
+/// `initialize`.
+///
+/// A supported version is always accepted as requested. Otherwise the server answers with the
+/// preferred version and lets the client decide whether to disconnect, as the specification
+/// says, or rejects the `initialize` request if the policy is strict.
+#[derive(Debug, Clone)]
+pub struct VersionPolicy {
+    supported: Vec<ProtocolVersion>,
+    preferred: ProtocolVersion,
+    strict: bool,
+}
+
+impl Default for VersionPolicy {
+    fn default() -> Self {
+        Self {
+            supported: ProtocolVersion::ALL.to_vec(),
+            preferred: ProtocolVersion::LATEST,
+            strict: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("unsupported protocol version: {requested}")]
+pub struct UnsupportedVersion {
+    pub requested: String,
+    pub supported: Vec<ProtocolVersion>,
+}
+
+impl VersionPolicy {
+    /// Accept only the versions. The preferred version becomes the latest of them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `versions` is empty.
+    pub fn supported(mut self, versions: impl IntoIterator<Item = ProtocolVersion>) -> Self {
+        self.supported = versions.into_iter().collect();
+        self.preferred = *self
+            .supported
+            .iter()
+            .max()
+            .expect("at least one protocol version must be supported");
+        self
+    }
+
+    /// The version answered to clients requesting an unsupported one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the version is not supported.
+    pub fn preferred(mut self, version: ProtocolVersion) -> Self {
+        assert!(
+            self.supported.contains(&version),
+            "the preferred protocol version {version} is not supported"
+        );
+        self.preferred = version;
+        self
+    }
+
+    /// Reject `initialize` with an error instead of answering with the preferred version.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Pick the version of a session from the one requested by the client.
+    pub fn negotiate(&self, requested: &str) -> Result<ProtocolVersion, UnsupportedVersion> {
+        match requested.parse::<ProtocolVersion>() {
+            Ok(version) if self.supported.contains(&version) => Ok(version),
+            _ if self.strict => Err(UnsupportedVersion {
+                requested: requested.to_string(),
+                supported: self.supported.clone(),
+            }),
+            _ => Ok(self.preferred),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let policy = VersionPolicy::default();
+        assert_eq!(
+            policy.negotiate("2024-11-05").unwrap(),
+            ProtocolVersion::V20241105
+        );
+        assert_eq!(
+            policy.negotiate("2099-01-01").unwrap(),
+            ProtocolVersion::LATEST
+        );
+
+        let policy = VersionPolicy::default()
+            .supported([ProtocolVersion::V20250326])
+            .strict(true);
+        assert_eq!(
+            policy.negotiate("2025-03-26").unwrap(),
+            ProtocolVersion::V20250326
+        );
+        assert!(policy.negotiate("2024-11-05").is_err());
+        assert!(policy.negotiate("invalid").is_err());
+    }
+}
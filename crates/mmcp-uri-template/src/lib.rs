@@ -0,0 +1,450 @@
+//! RFC 6570 URI templates for the MMCP framework, matched against concrete URIs to read resource
+//! templates. Free of dependencies, so that the procedural macros can validate templates cheaply.
+
+use std::collections::{BTreeMap, HashSet};
+
+/// Characters that must be percent-encoded in unreserved expansions (RFC 3986 gen-delims and
+/// sub-delims).
+const RESERVED: &str = ":/?#[]@!$&'()*+,;=";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UriTemplateError {
+    UnclosedExpression(String),
+    UnexpectedClose(String),
+    EmptyExpression(String),
+    InvalidVariable(String, String),
+}
+
+impl std::fmt::Display for UriTemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnclosedExpression(template) => {
+                write!(f, "unclosed expression in URI template: {template}")
+            }
+            Self::UnexpectedClose(template) => {
+                write!(f, "unexpected `}}` in URI template: {template}")
+            }
+            Self::EmptyExpression(template) => {
+                write!(f, "empty expression in URI template: {template}")
+            }
+            Self::InvalidVariable(template, variable) => {
+                write!(
+                    f,
+                    "invalid variable name `{variable}` in URI template: {template}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for UriTemplateError {}
+
+/// An RFC 6570 URI template that can be matched against concrete URIs.
+///
+/// Matching is the reverse of expansion: given `db://{table}/{id}` and `db://users/42`, it
+/// extracts `table = "users"` and `id = "42"`. All operators of level 3 are supported. The
+/// prefix (`:n`) and explode (`*`) modifiers are accepted but ignored while matching.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UriTemplate {
+    template: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Expression {
+        operator: Operator,
+        variables: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    /// `{var}`
+    Simple,
+    /// `{+var}`
+    Reserved,
+    /// `{#var}`
+    Fragment,
+    /// `{.var}`
+    Label,
+    /// `{/var}`
+    Path,
+    /// `{;var}`
+    PathParameter,
+    /// `{?var}`
+    Query,
+    /// `{&var}`
+    QueryContinuation,
+}
+
+impl Operator {
+    fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            '+' => Self::Reserved,
+            '#' => Self::Fragment,
+            '.' => Self::Label,
+            '/' => Self::Path,
+            ';' => Self::PathParameter,
+            '?' => Self::Query,
+            '&' => Self::QueryContinuation,
+            _ => return None,
+        })
+    }
+
+    fn prefix(self) -> Option<char> {
+        match self {
+            Self::Simple | Self::Reserved => None,
+            Self::Fragment => Some('#'),
+            Self::Label => Some('.'),
+            Self::Path => Some('/'),
+            Self::PathParameter => Some(';'),
+            Self::Query => Some('?'),
+            Self::QueryContinuation => Some('&'),
+        }
+    }
+
+    fn separator(self) -> char {
+        match self {
+            Self::Simple | Self::Reserved | Self::Fragment => ',',
+            Self::Label => '.',
+            Self::Path => '/',
+            Self::PathParameter => ';',
+            Self::Query | Self::QueryContinuation => '&',
+        }
+    }
+
+    fn named(self) -> bool {
+        matches!(
+            self,
+            Self::PathParameter | Self::Query | Self::QueryContinuation
+        )
+    }
+
+    fn allow_reserved(self) -> bool {
+        matches!(self, Self::Reserved | Self::Fragment)
+    }
+}
+
+impl UriTemplate {
+    pub fn parse(template: &str) -> Result<Self, UriTemplateError> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            match rest.find(['{', '}']) {
+                Some(index) if rest[index..].starts_with('}') => {
+                    return Err(UriTemplateError::UnexpectedClose(template.to_string()));
+                }
+                Some(start) => {
+                    if start > 0 {
+                        parts.push(Part::Literal(rest[..start].to_string()));
+                    }
+                    let Some(end) = rest[start..].find('}') else {
+                        return Err(UriTemplateError::UnclosedExpression(template.to_string()));
+                    };
+                    parts.push(parse_expression(template, &rest[start + 1..start + end])?);
+                    rest = &rest[start + end + 1..];
+                }
+                None => {
+                    parts.push(Part::Literal(rest.to_string()));
+                    rest = "";
+                }
+            }
+        }
+        Ok(Self {
+            template: template.to_string(),
+            parts,
+        })
+    }
+
+    /// The template string this was parsed from.
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// The names of the variables in this template, in order of appearance.
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts
+            .iter()
+            .flat_map(|part| match part {
+                Part::Literal(_) => [].iter(),
+                Part::Expression { variables, .. } => variables.iter(),
+            })
+            .map(String::as_str)
+    }
+
+    /// Match a URI against this template, returning the percent-decoded values of the variables.
+    ///
+    /// Variables that are absent from the URI (e.g. an omitted `{?query}`) are not included.
+    pub fn match_uri(&self, uri: &str) -> Option<BTreeMap<String, String>> {
+        let mut variables = BTreeMap::new();
+        match_parts(&self.parts, uri, &mut variables).then_some(variables)
+    }
+}
+
+impl std::fmt::Display for UriTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+impl std::str::FromStr for UriTemplate {
+    type Err = UriTemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+fn parse_expression(template: &str, expression: &str) -> Result<Part, UriTemplateError> {
+    let mut chars = expression.chars();
+    let (operator, list) = match chars.next().and_then(Operator::from_char) {
+        Some(operator) => (operator, chars.as_str()),
+        None => (Operator::Simple, expression),
+    };
+    if list.is_empty() {
+        return Err(UriTemplateError::EmptyExpression(template.to_string()));
+    }
+    let variables = list
+        .split(',')
+        .map(|spec| {
+            let name = spec
+                .split_once(':')
+                .map_or(spec, |(name, _)| name)
+                .trim_end_matches('*');
+            let valid = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '%'));
+            if valid {
+                Ok(name.to_string())
+            } else {
+                Err(UriTemplateError::InvalidVariable(
+                    template.to_string(),
+                    spec.to_string(),
+                ))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Part::Expression {
+        operator,
+        variables,
+    })
+}
+
+fn match_parts(parts: &[Part], uri: &str, variables: &mut BTreeMap<String, String>) -> bool {
+    match_from(parts, 0, uri, 0, &mut HashSet::new(), variables)
+}
+
+/// Match `uri[offset..]` against `parts[index..]`, binding the variables only once the rest has
+/// matched. Positions that failed to match are remembered in `failed`, so that each is tried once.
+fn match_from(
+    parts: &[Part],
+    index: usize,
+    uri: &str,
+    offset: usize,
+    failed: &mut HashSet<(usize, usize)>,
+    variables: &mut BTreeMap<String, String>,
+) -> bool {
+    let Some(part) = parts.get(index) else {
+        return offset == uri.len();
+    };
+    if failed.contains(&(index, offset)) {
+        return false;
+    }
+    let rest = &uri[offset..];
+    let matched = match part {
+        Part::Literal(literal) => {
+            rest.starts_with(literal.as_str())
+                && match_from(
+                    parts,
+                    index + 1,
+                    uri,
+                    offset + literal.len(),
+                    failed,
+                    variables,
+                )
+        }
+        Part::Expression {
+            operator,
+            variables: names,
+        } => expansion_ends(parts.get(index + 1), rest).any(|end| {
+            let Some(values) = match_expression(*operator, names, &rest[..end]) else {
+                return false;
+            };
+            if !match_from(parts, index + 1, uri, offset + end, failed, variables) {
+                return false;
+            }
+            variables.extend(values);
+            true
+        }),
+    };
+    if !matched {
+        failed.insert((index, offset));
+    }
+    matched
+}
+
+/// The possible lengths of an expansion followed by the part, shortest first so that following
+/// literals can anchor the match: up to an occurrence of the literal, or the rest of the URI for
+/// the last part.
+fn expansion_ends<'a>(next: Option<&'a Part>, rest: &'a str) -> impl Iterator<Item = usize> + 'a {
+    (0..=rest.len()).filter(move |&end| {
+        rest.is_char_boundary(end)
+            && match next {
+                None => end == rest.len(),
+                Some(Part::Literal(literal)) => rest[end..].starts_with(literal.as_str()),
+                Some(Part::Expression { .. }) => true,
+            }
+    })
+}
+
+fn match_expression(
+    operator: Operator,
+    names: &[String],
+    expansion: &str,
+) -> Option<Vec<(String, String)>> {
+    let body = match operator.prefix() {
+        // An empty expansion means every variable is undefined.
+        Some(_) if expansion.is_empty() => return Some(Vec::new()),
+        Some(prefix) => expansion.strip_prefix(prefix)?,
+        None if expansion.is_empty() => return None,
+        None => expansion,
+    };
+
+    let pieces = if names.len() == 1 && !operator.named() {
+        vec![body]
+    } else {
+        body.split(operator.separator()).collect()
+    };
+    if !operator.named() && pieces.len() > names.len() {
+        return None;
+    }
+
+    let mut values = Vec::new();
+    for (index, piece) in pieces.into_iter().enumerate() {
+        let (name, value) = if operator.named() {
+            let (name, value) = piece.split_once('=').unwrap_or((piece, ""));
+            (names.iter().find(|n| n.as_str() == name)?, value)
+        } else {
+            (&names[index], piece)
+        };
+        if !operator.allow_reserved() && value.contains(|c| RESERVED.contains(c)) {
+            return None;
+        }
+        values.push((name.clone(), percent_decode(value)?));
+    }
+    Some(values)
+}
+
+/// Decode the `%XX` escapes of a URI component, or `None` if an escape or the decoded bytes are
+/// invalid.
+pub fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Option<BTreeMap<String, String>> {
+        Some(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_simple() {
+        let template = UriTemplate::parse("db://{table}/{id}").unwrap();
+        assert_eq!(
+            template.variables().collect::<Vec<_>>(),
+            vec!["table", "id"]
+        );
+        assert_eq!(
+            template.match_uri("db://users/42"),
+            vars(&[("table", "users"), ("id", "42")])
+        );
+        assert_eq!(
+            template.match_uri("db://users/hello%20world"),
+            vars(&[("table", "users"), ("id", "hello world")])
+        );
+        assert_eq!(template.match_uri("db://users/42/extra"), None);
+        assert_eq!(template.match_uri("db://users/"), None);
+        assert_eq!(template.match_uri("file://users/42"), None);
+    }
+
+    #[test]
+    fn test_operators() {
+        let template = UriTemplate::parse("file:///{+path}").unwrap();
+        assert_eq!(
+            template.match_uri("file:///etc/hosts"),
+            vars(&[("path", "etc/hosts")])
+        );
+
+        let template = UriTemplate::parse("repo://{owner}{/name,branch}").unwrap();
+        assert_eq!(
+            template.match_uri("repo://ryo33/mmcp/main"),
+            vars(&[("owner", "ryo33"), ("name", "mmcp"), ("branch", "main")])
+        );
+        assert_eq!(
+            template.match_uri("repo://ryo33/mmcp"),
+            vars(&[("owner", "ryo33"), ("name", "mmcp")])
+        );
+
+        let template = UriTemplate::parse("search://items{?q,limit}").unwrap();
+        assert_eq!(
+            template.match_uri("search://items?limit=10&q=rust"),
+            vars(&[("q", "rust"), ("limit", "10")])
+        );
+        assert_eq!(template.match_uri("search://items"), vars(&[]));
+        assert_eq!(template.match_uri("search://items?unknown=1"), None);
+    }
+
+    #[test]
+    fn test_backtracking() {
+        // Each expression could end anywhere, which must not be tried for every combination
+        let template = UriTemplate::parse("x://{a}{b}{c}{d}{e}{f}{g}{h}/end").unwrap();
+        let uri = format!("x://{}/nope", "a".repeat(100));
+        assert_eq!(template.match_uri(&uri), None);
+
+        let template = UriTemplate::parse("x://{+a}/{+b}/end").unwrap();
+        assert_eq!(
+            template.match_uri("x://1/2/3/end"),
+            vars(&[("a", "1"), ("b", "2/3")])
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            UriTemplate::parse("db://{table"),
+            Err(UriTemplateError::UnclosedExpression(_))
+        ));
+        assert!(matches!(
+            UriTemplate::parse("db://table}"),
+            Err(UriTemplateError::UnexpectedClose(_))
+        ));
+        assert!(matches!(
+            UriTemplate::parse("db://{}"),
+            Err(UriTemplateError::EmptyExpression(_))
+        ));
+        assert!(matches!(
+            UriTemplate::parse("db://{a b}"),
+            Err(UriTemplateError::InvalidVariable(_, _))
+        ));
+    }
+}
@@ -1,7 +1,7 @@
 pub mod schemars;
 
 #[cfg(feature = "macros")]
-pub use mmcp_macros::tool;
+pub use mmcp_macros::{prompt, resource, tool};
 
 #[cfg(feature = "macros")]
 pub use serde;
@@ -14,6 +14,17 @@ pub mod server {
 
     #[cfg(feature = "server-stdio")]
     pub use mmcp_server_stdio::stdio_server_rpc;
+
+    #[cfg(feature = "server-axum")]
+    pub use mmcp_server_axum as axum;
+}
+
+#[cfg(feature = "client")]
+pub mod client {
+    pub use mmcp_client::*;
+
+    #[cfg(feature = "client-stdio")]
+    pub use mmcp_client_stdio::{StdioClientRpc, stdio_client_rpc};
 }
 
 pub use mmcp_protocol as protocol;
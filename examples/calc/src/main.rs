@@ -1,11 +1,28 @@
+use std::sync::{Arc, Mutex};
+
 use mmcp::{
-    server::{MCPServer, primitives::tool::Text, stdio_server_rpc},
+    prompt,
+    protocol::ProtocolVersion,
+    resource,
+    schemars::JsonSchema,
+    serde::Serialize,
+    server::{
+        MCPServer,
+        primitives::{
+            prompt::Assistant,
+            tool::{Json, State, Text, ToolContext},
+        },
+        stdio_server_rpc,
+        version::VersionPolicy,
+    },
     tool,
 };
+use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _};
 
 #[tool]
 /// Add two numbers
 async fn add(x: i32, y: i32) -> Text<i32> {
+    tracing::info!(x, y, "adding");
     Text(x + y)
 }
 
@@ -15,11 +32,111 @@ async fn sub(x: i32, y: i32) -> Text<i32> {
     Text(x - y)
 }
 
+#[tool]
+/// Multiply two numbers by repeated addition, reporting the progress
+async fn mul(x: i32, y: i32, ctx: &ToolContext) -> Text<i32> {
+    let mut product = 0;
+    for i in 0..y.abs() {
+        if ctx.is_cancelled() {
+            break;
+        }
+        product += x;
+        let _ = ctx
+            .report_progress(i as f64 + 1.0, Some(y.abs() as f64))
+            .await;
+    }
+    Text(if y < 0 { -product } else { product })
+}
+
+/// The result of an integer division
+#[derive(Serialize, JsonSchema)]
+#[serde(crate = "mmcp::serde")]
+#[schemars(crate = "mmcp::schemars")]
+pub struct Division {
+    pub quotient: i32,
+    pub remainder: i32,
+}
+
+#[tool]
+/// Divide two numbers, returning the quotient and the remainder
+fn div(x: i32, y: i32) -> Result<Json<Division>, String> {
+    if y == 0 {
+        return Err("Division by zero".into());
+    }
+    Ok(Json(Division {
+        quotient: x / y,
+        remainder: x % y,
+    }))
+}
+
+/// The memory of the calculator, shared by every session
+#[derive(Clone, Default)]
+struct Memory(Arc<Mutex<i32>>);
+
+#[tool]
+/// Store a number in the memory
+fn store(State(memory): State<Memory>, value: i32) -> Text<i32> {
+    *memory.0.lock().unwrap() = value;
+    Text(value)
+}
+
+#[tool]
+/// Recall the number stored in the memory
+fn recall(State(memory): State<Memory>) -> Text<i32> {
+    Text(*memory.0.lock().unwrap())
+}
+
+/// The operations supported by this calculator
+#[resource(uri = "calc://operations", mime_type = "text/plain")]
+fn operations() -> &'static str {
+    "add, sub, mul, div, store, recall"
+}
+
+/// The sum of two numbers
+#[resource(uri = "calc://sum/{x}/{y}", mime_type = "text/plain")]
+fn sum(x: i32, y: i32) -> Text<i32> {
+    Text(x + y)
+}
+
+/// Suggest example expressions starting with the given input
+async fn complete_expression(value: String) -> Vec<String> {
+    ["1 + 2", "3 - 4", "1 + 2 - 3"]
+        .into_iter()
+        .filter(|expression| expression.starts_with(&value))
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Explain how to calculate an expression step by step
+#[prompt]
+fn explain(
+    #[arg(description = "The expression to explain", complete = complete_expression)]
+    expression: String,
+) -> (String, Assistant<&'static str>) {
+    (
+        format!("Explain how to calculate `{expression}` step by step."),
+        Assistant("Sure, let's break it down."),
+    )
+}
+
 #[tokio::main]
 async fn main() {
-    MCPServer::new("mmcp-uuid", env!("CARGO_PKG_VERSION"))
+    let server = MCPServer::new("mmcp-uuid", env!("CARGO_PKG_VERSION"))
         .with_tools_from_inventory()
-        .start(stdio_server_rpc())
-        .await
-        .unwrap();
+        .with_resources_from_inventory()
+        .with_prompts_from_inventory()
+        .with_state(Memory::default())
+        // Opt in to 2025-06-18 so that `div` returns structured content
+        .with_version_policy(VersionPolicy::default().supported([
+            ProtocolVersion::V20241105,
+            ProtocolVersion::V20250326,
+            ProtocolVersion::V20250618,
+        ]));
+
+    // Forward logs to the client, which chooses the level with `logging/setLevel`
+    tracing_subscriber::registry()
+        .with(server.logging_layer())
+        .init();
+
+    server.start(stdio_server_rpc()).await.unwrap();
 }
@@ -97,16 +97,7 @@ mod tests {
                 "jsonrpc": "2.0",
                 "result": {
                     "capabilities": {
-                        "prompts": {
-                            "listChanged": true
-                        },
-                        "resources": {
-                            "listChanged": true,
-                            "subscribe": false,
-                        },
-                        "tools": {
-                            "listChanged": true,
-                        }
+                        "tools": {}
                     },
                     "protocolVersion": "2025-03-26",
                     "serverInfo": {